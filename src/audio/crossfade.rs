@@ -0,0 +1,159 @@
+use crate::QueueKey;
+use crate::audio::filters::MIN_SPEED;
+use crate::audio::loudness;
+use crate::commands::play::songbird_manager;
+use crate::settings::settings::{Transition, settings};
+use serenity::all::GuildId;
+use serenity::async_trait;
+use serenity::prelude::TypeMap;
+use songbird::tracks::TrackHandle;
+use songbird::{Event, EventContext, EventHandler};
+use std::collections::VecDeque;
+use std::f32::consts::FRAC_PI_2;
+use std::sync::{Arc, PoisonError};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, info_span};
+use tracing_futures::Instrument;
+
+/// How often a playing track checks whether to hand over to the next one.
+const CHECK_PERIOD: Duration = Duration::from_millis(500);
+/// Interval between two volume changes of a crossfade.
+const RAMP_STEP: Duration = Duration::from_millis(100);
+
+/// Starts the next track before this one ends when the guild crossfades. Every track of the
+/// queue is watched, so that changing the setting applies to the tracks already queued.
+pub fn watch(track: &TrackHandle, data: Arc<RwLock<TypeMap>>, guild_id: GuildId) {
+    track
+        .add_event(
+            Event::Periodic(CHECK_PERIOD, None),
+            Crossfade { data, guild_id },
+        )
+        .unwrap_or_default();
+}
+
+struct Crossfade {
+    data: Arc<RwLock<TypeMap>>,
+    guild_id: GuildId,
+}
+
+#[async_trait]
+impl EventHandler for Crossfade {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track([(state, outgoing)]) = ctx else {
+            return None;
+        };
+
+        let settings = settings(&self.data, self.guild_id).await;
+        if settings.transition != Transition::Crossfade {
+            return None;
+        }
+
+        let queue_lock = {
+            let guard = self.data.read().await;
+            guard.get::<QueueKey>().cloned()
+        }?;
+
+        let mut maybe_queue = queue_lock.write().await;
+        let queue = maybe_queue.get_mut(&self.guild_id)?;
+
+        if queue.repeat || queue.crossfading.is_some() || queue.is_last() {
+            return None;
+        }
+
+        // Livestreams have no end to fade out of
+        let duration = queue.queue.get(queue.playing_index)?.metadata.duration?;
+        let speed = queue
+            .filters
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .speed
+            .max(MIN_SPEED as f32);
+        if state.position + settings.crossfade() < duration.div_f32(speed) {
+            return None;
+        }
+
+        let manager = songbird_manager(&self.data).await.ok()?;
+        let handler_lock = manager.get(self.guild_id)?;
+        let incoming = {
+            let handler = handler_lock.lock().await;
+            let tracks = handler.queue().current_queue();
+            // Recreated or skipped meanwhile, a newer track is watched instead
+            if tracks.first()?.uuid() != outgoing.uuid() {
+                return Some(Event::Cancel);
+            }
+            let incoming = tracks.get(1)?.clone();
+
+            // The incoming track is the current one from now on, for Songbird too so that
+            // jumping, seeking, inserting and pausing all act on it
+            handler
+                .queue()
+                .modify_queue(|tracks| hand_over(&mut queue.playing_index, tracks));
+            incoming
+        };
+
+        let outgoing = (*outgoing).clone();
+        queue.crossfading = Some(outgoing.clone());
+        drop(maybe_queue);
+
+        debug!(overlap = ?settings.crossfade(), "Crossfading into the next track");
+
+        incoming.set_volume(0.0).unwrap_or_default();
+        incoming.play().unwrap_or_default();
+
+        let volume = settings.volume();
+        let overlap = settings.crossfade();
+        tokio::spawn(
+            async move {
+                let steps = (overlap.as_millis() / RAMP_STEP.as_millis()).max(1) as u32;
+
+                for step in 1..=steps {
+                    tokio::time::sleep(RAMP_STEP).await;
+
+                    // Equal power, so the loudness holds steady halfway through
+                    let angle = step as f32 / steps as f32 * FRAC_PI_2;
+                    let _ = outgoing.set_volume(volume * loudness::gain(&outgoing) * angle.cos());
+                    let _ = incoming.set_volume(volume * loudness::gain(&incoming) * angle.sin());
+                }
+
+                // Whatever is left of it is silent, and no longer in Songbird's queue
+                let _ = outgoing.stop();
+            }
+            .instrument(info_span!("crossfade", guild_id = self.guild_id.get())),
+        );
+
+        Some(Event::Cancel)
+    }
+}
+
+/// Moves both queues on to the next track, leaving `playing_index` and the head of Songbird's
+/// queue on the same track. Returns the outgoing track, which is no longer queued.
+fn hand_over<T>(playing_index: &mut usize, tracks: &mut VecDeque<T>) -> Option<T> {
+    let outgoing = tracks.pop_front()?;
+    *playing_index += 1;
+    Some(outgoing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::prev::move_last;
+
+    #[test]
+    fn hands_over_keeping_both_queues_aligned() {
+        let mut queue = vec!["a", "b", "c", "d"];
+        let mut tracks = VecDeque::from(queue.clone());
+        let mut playing_index = 0;
+
+        assert_eq!(hand_over(&mut playing_index, &mut tracks), Some("a"));
+        assert_eq!(playing_index, 1);
+        assert_eq!(tracks.front(), queue.get(playing_index));
+
+        // Playing next during the overlap lands right after the incoming track
+        let index = playing_index + 1;
+        queue.insert(index, "x");
+        tracks.push_back("x");
+        move_last(&mut tracks, 1, index - playing_index);
+        assert_eq!(tracks, VecDeque::from(queue.split_off(playing_index)));
+    }
+}
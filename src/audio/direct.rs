@@ -0,0 +1,206 @@
+use crate::audio::source::{Source, describe};
+use reqwest::Client;
+use reqwest::header::{CONTENT_LENGTH, CONTENT_TYPE, HeaderMap};
+use songbird::input::AuxMetadata;
+use std::time::Duration;
+use url::Url;
+
+/// Extensions of links played without yt-dlp.
+pub const AUDIO_EXTENSIONS: [&str; 9] = [
+    "mp3", "ogg", "oga", "opus", "flac", "wav", "m4a", "aac", "weba",
+];
+/// Time to wait for the headers of a link before giving it to yt-dlp.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What the headers of an audio link tell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Probe {
+    /// Station name of an internet radio
+    pub name: Option<String>,
+    /// Whether it is a stream rather than a file
+    pub live: bool,
+}
+
+fn extension(url: &Url) -> Option<String> {
+    let file = url.path_segments()?.next_back()?;
+    let (_, extension) = file.rsplit_once('.')?;
+    Some(extension.to_lowercase())
+}
+
+/// Whether yt-dlp handles this link, skipping the header check for the most common links.
+pub fn is_youtube(url: &str) -> bool {
+    Url::parse(url).ok().is_some_and(|url| {
+        url.host_str().is_some_and(|host| {
+            host == "youtu.be" || host == "youtube.com" || host.ends_with(".youtube.com")
+        })
+    })
+}
+
+/// Whether a link is an `.m3u` or `.pls` playlist, HLS `.m3u8` being left to yt-dlp.
+pub fn is_playlist(url: &str) -> bool {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| extension(&url))
+        .is_some_and(|extension| extension == "m3u" || extension == "pls")
+}
+
+/// Checks whether a link serves audio from the headers of a request, dropped before the body.
+/// Radios answer with `icy-` headers, or at least without a length.
+pub async fn probe(client: &Client, url: &str) -> Option<Probe> {
+    let response = client
+        .get(url)
+        .header("Icy-MetaData", "1")
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    let headers = response.headers();
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let content_type = header(CONTENT_TYPE.as_str())
+        .map(|content_type| content_type.to_lowercase())
+        .unwrap_or_default();
+    let has_audio_extension = extension(response.url())
+        .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension.as_str()));
+    let audio = match content_type.split(';').next().unwrap_or_default() {
+        "audio/x-mpegurl" | "audio/mpegurl" | "audio/x-scpls" => false,
+        "application/ogg" => true,
+        "" | "application/octet-stream" => has_audio_extension,
+        content_type => content_type.starts_with("audio/"),
+    };
+    if !audio {
+        return None;
+    }
+
+    Some(Probe {
+        name: header("icy-name"),
+        live: is_stream(headers),
+    })
+}
+
+fn is_stream(headers: &HeaderMap) -> bool {
+    headers.keys().any(|name| name.as_str().starts_with("icy-"))
+        || !headers.contains_key(CONTENT_LENGTH)
+}
+
+/// Metadata of an audio link: the station name of a radio, or the tags of a file read from its
+/// start. Falls back to the file name.
+pub async fn metadata(url: &str, probe: &Probe, src: &mut Source) -> AuxMetadata {
+    let mut metadata = if probe.live {
+        AuxMetadata {
+            title: probe.name.clone(),
+            channel: probe.name.clone(),
+            ..AuxMetadata::default()
+        }
+    } else {
+        describe(src).await.unwrap_or_default()
+    };
+
+    metadata.source_url = Some(String::from(url));
+    if metadata.title.is_none() {
+        metadata.title = Url::parse(url).ok().and_then(|url| {
+            url.path_segments()?
+                .next_back()
+                .filter(|file| !file.is_empty())
+                .map(String::from)
+        });
+    }
+    metadata
+}
+
+/// Name and entries of an `.m3u` or `.pls` playlist.
+pub async fn playlist(client: &Client, url: &str) -> Option<(String, Vec<String>)> {
+    let base = Url::parse(url).ok()?;
+    let content = client
+        .get(url)
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .text()
+        .await
+        .ok()?;
+
+    let entries = if extension(&base).as_deref() == Some("pls") {
+        parse_pls(&base, &content)
+    } else {
+        parse_m3u(&base, &content)
+    };
+    let name = base
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(String::from)
+        .unwrap_or(String::from(url));
+
+    Some((name, entries)).filter(|(_, entries)| !entries.is_empty())
+}
+
+/// Entries of an M3U playlist, one per line besides the `#` directives.
+fn parse_m3u(base: &Url, content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| base.join(line).ok())
+        .map(String::from)
+        .collect()
+}
+
+/// Entries of a PLS playlist, given as `FileN=<link>`.
+fn parse_pls(base: &Url, content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| line.trim().split_once('='))
+        .filter(|(key, _)| key.to_lowercase().starts_with("file"))
+        .filter_map(|(_, entry)| base.join(entry.trim()).ok())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_links() {
+        assert!(is_youtube("https://www.youtube.com/watch?v=dQw4w9WgXcQ"));
+        assert!(is_youtube("https://youtu.be/dQw4w9WgXcQ"));
+        assert!(!is_youtube("https://example.com/song.mp3"));
+        assert!(is_playlist("http://radio.example.com/listen.PLS"));
+        assert!(is_playlist("http://radio.example.com/stream.m3u?token=1"));
+        assert!(!is_playlist("https://example.com/live/index.m3u8"));
+    }
+
+    #[test]
+    fn parses_playlists() {
+        let base = Url::parse("http://radio.example.com/lists/station.m3u").unwrap();
+        let m3u =
+            "#EXTM3U\n#EXTINF:-1,Station\nhttp://stream.example.com:8000/live\n\nbackup.mp3\n";
+        assert_eq!(
+            parse_m3u(&base, m3u),
+            [
+                "http://stream.example.com:8000/live",
+                "http://radio.example.com/lists/backup.mp3"
+            ]
+        );
+
+        let pls = "[playlist]\nNumberOfEntries=2\nFile1=http://stream.example.com:8000/;\nTitle1=Station\nfile2 = http://backup.example.com/live\nVersion=2\n";
+        assert_eq!(
+            parse_pls(&base, pls),
+            [
+                "http://stream.example.com:8000/;",
+                "http://backup.example.com/live"
+            ]
+        );
+    }
+}
@@ -0,0 +1,751 @@
+use crate::audio::loudness::Biquad;
+use crate::audio::source::Source;
+use serenity::async_trait;
+use songbird::input::codecs::{get_codec_registry, get_probe};
+use songbird::input::{AudioStream, AudioStreamError, AuxMetadata, Compose, Input};
+use std::f64::consts::PI;
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Seek, SeekFrom};
+use std::sync::{Arc, PoisonError, RwLock};
+use symphonia::core::audio::{SampleBuffer, SignalSpec};
+use symphonia::core::codecs::{Decoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::units::Time;
+
+pub const MIN_SPEED: f64 = 0.5;
+pub const MAX_SPEED: f64 = 2.0;
+/// Pitch shift bound, in semitones either way.
+pub const MAX_PITCH: f64 = 12.0;
+/// Equaliser gain bound, in dB either way.
+pub const MAX_GAIN_DB: f64 = 12.0;
+
+/// Size of the header of Songbird's raw PCM container, see `songbird::input::codecs::RawReader`.
+const HEADER_LEN: u64 = 16;
+/// Window of the pitch shifter, in samples. Longer is smoother but smears transients.
+const PITCH_WINDOW: usize = 2048;
+
+/// Effects applied to the audio of a guild. Changes apply right away to the playing track, unless
+/// it was loaded without any, see [`input`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Filters {
+    /// Playback rate, changing tempo and pitch together like a turntable
+    pub speed: f32,
+    /// Pitch shift in semitones, keeping the tempo
+    pub pitch: f32,
+    /// Equaliser gains in dB, around 100Hz, 1kHz and 8kHz
+    pub bass: f32,
+    pub mid: f32,
+    pub treble: f32,
+    /// Turns of the sound around the listener per second, for the 8D effect
+    pub rotation: f32,
+    /// Cancels what is mixed in the centre, usually the vocals
+    pub karaoke: bool,
+}
+
+impl Default for Filters {
+    fn default() -> Self {
+        Filters {
+            speed: 1.0,
+            pitch: 0.0,
+            bass: 0.0,
+            mid: 0.0,
+            treble: 0.0,
+            rotation: 0.0,
+            karaoke: false,
+        }
+    }
+}
+
+/// Filters of a guild, shared with the decoding of its tracks.
+pub type SharedFilters = Arc<RwLock<Filters>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    BassBoost,
+    Nightcore,
+    Vaporwave,
+    EightD,
+    Karaoke,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 5] = [
+        Preset::BassBoost,
+        Preset::Nightcore,
+        Preset::Vaporwave,
+        Preset::EightD,
+        Preset::Karaoke,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::BassBoost => "bassboost",
+            Preset::Nightcore => "nightcore",
+            Preset::Vaporwave => "vaporwave",
+            Preset::EightD => "8d",
+            Preset::Karaoke => "karaoke",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Preset> {
+        Preset::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+
+    pub fn filters(self) -> Filters {
+        let flat = Filters::default();
+        match self {
+            Preset::BassBoost => Filters { bass: 8.0, ..flat },
+            Preset::Nightcore => Filters {
+                speed: 1.25,
+                ..flat
+            },
+            Preset::Vaporwave => Filters {
+                speed: 0.8,
+                bass: 3.0,
+                ..flat
+            },
+            Preset::EightD => Filters {
+                rotation: 0.125,
+                ..flat
+            },
+            Preset::Karaoke => Filters {
+                karaoke: true,
+                ..flat
+            },
+        }
+    }
+}
+
+impl Filters {
+    pub fn is_flat(&self) -> bool {
+        *self == Filters::default()
+    }
+
+    /// Short description for the now-playing message, the preset name when it is one. `None`
+    /// without any effect.
+    pub fn describe(&self) -> Option<String> {
+        if self.is_flat() {
+            return None;
+        }
+        if let Some(preset) = Preset::ALL
+            .into_iter()
+            .find(|preset| preset.filters() == *self)
+        {
+            return Some(String::from(preset.name()));
+        }
+
+        let mut parts = Vec::new();
+        if self.speed != 1.0 {
+            parts.push(format!("speed ×{}", self.speed));
+        }
+        if self.pitch != 0.0 {
+            parts.push(format!("pitch {:+}", self.pitch));
+        }
+        for (name, gain) in [
+            ("bass", self.bass),
+            ("mid", self.mid),
+            ("treble", self.treble),
+        ] {
+            if gain != 0.0 {
+                parts.push(format!("{} {:+}dB", name, gain));
+            }
+        }
+        if self.rotation != 0.0 {
+            parts.push(String::from("8d"));
+        }
+        if self.karaoke {
+            parts.push(String::from("karaoke"));
+        }
+
+        Some(parts.join(", "))
+    }
+}
+
+/// Wraps a source so that its audio goes through the guild filters before reaching the mixer.
+/// Tracks loaded while the guild has no filter are passed through as they are, and only pick up
+/// filters set later from the next track on.
+pub fn input(src: Source, filters: SharedFilters) -> Input {
+    Input::Lazy(Box::new(Filtered { src, filters }))
+}
+
+struct Filtered {
+    src: Source,
+    filters: SharedFilters,
+}
+
+#[async_trait]
+impl Compose for Filtered {
+    fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        // yt-dlp and HTTP sources are only created asynchronously
+        Err(AudioStreamError::Unsupported)
+    }
+
+    async fn create_async(
+        &mut self,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        let stream = self.src.create_async().await?;
+        let filters = self.filters.clone();
+
+        // Left to Songbird's own decoding unless there is something to apply
+        if filters
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_flat()
+        {
+            return Ok(stream);
+        }
+
+        // Probing reads the start of the stream, which blocks
+        let filtered = tokio::task::spawn_blocking(move || FilteredStream::new(stream, filters))
+            .await
+            .map_err(|error| AudioStreamError::Fail(Box::new(error)))??;
+
+        Ok(AudioStream {
+            input: Box::new(filtered),
+            hint: None,
+        })
+    }
+
+    fn should_create_async(&self) -> bool {
+        true
+    }
+
+    async fn aux_metadata(&mut self) -> Result<AuxMetadata, AudioStreamError> {
+        self.src.aux_metadata().await
+    }
+}
+
+/// Decodes a source and hands the filtered samples to Songbird as raw PCM.
+struct FilteredStream {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    spec: SignalSpec,
+    /// Whether the source can seek backwards, forward seeks decode and drop audio otherwise
+    seekable: bool,
+    filters: SharedFilters,
+    chain: Chain,
+    /// Encoded output not read yet, from `offset`
+    pending: Vec<u8>,
+    offset: usize,
+    /// Bytes of output read so far, header included
+    position: u64,
+}
+
+fn fail(error: SymphoniaError) -> AudioStreamError {
+    AudioStreamError::Fail(Box::new(error))
+}
+
+impl FilteredStream {
+    fn new(
+        stream: AudioStream<Box<dyn MediaSource>>,
+        filters: SharedFilters,
+    ) -> Result<Self, AudioStreamError> {
+        let seekable = stream.input.is_seekable();
+        let hint = stream.hint.unwrap_or_default();
+        let source = MediaSourceStream::new(stream.input, Default::default());
+        let mut format = get_probe()
+            .format(
+                &hint,
+                source,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(fail)?
+            .format;
+
+        let track = format
+            .default_track()
+            .ok_or(AudioStreamError::Unsupported)?;
+        let track_id = track.id;
+        let mut decoder = get_codec_registry()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .map_err(fail)?;
+
+        // The header needs the output format, only known for sure once decoding
+        let (spec, samples) =
+            decode(&mut *format, &mut *decoder, track_id).ok_or(AudioStreamError::Unsupported)?;
+
+        let mut pending = b"SbirdRaw".to_vec();
+        pending.extend_from_slice(&spec.rate.to_le_bytes());
+        pending.extend_from_slice(&(spec.channels.count() as u32).to_le_bytes());
+
+        let mut stream = FilteredStream {
+            format,
+            decoder,
+            track_id,
+            spec,
+            seekable,
+            filters,
+            chain: Chain::new(spec.channels.count(), spec.rate),
+            pending,
+            offset: 0,
+            position: 0,
+        };
+        stream.push(&samples);
+
+        Ok(stream)
+    }
+
+    fn filters(&self) -> Filters {
+        *self.filters.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Filters decoded samples and queues them for reading.
+    fn push(&mut self, samples: &[f32]) {
+        let filters = self.filters();
+        let mut output = Vec::with_capacity(samples.len());
+        self.chain.process(filters, samples, &mut output);
+
+        self.pending
+            .extend(output.iter().flat_map(|sample| sample.to_le_bytes()));
+    }
+
+    /// Decodes the next packet into `pending`, `false` at the end of the track.
+    fn fill(&mut self) -> bool {
+        self.pending.clear();
+        self.offset = 0;
+
+        while let Some((spec, samples)) =
+            decode(&mut *self.format, &mut *self.decoder, self.track_id)
+        {
+            // The header is already out, a packet in another layout can't be played
+            if spec.channels.count() != self.spec.channels.count() {
+                continue;
+            }
+            self.push(&samples);
+            return true;
+        }
+
+        false
+    }
+}
+
+/// Decodes the next packet of a track into interleaved samples, skipping corrupt packets. `None`
+/// at the end of the track.
+fn decode(
+    format: &mut dyn FormatReader,
+    decoder: &mut dyn Decoder,
+    track_id: u32,
+) -> Option<(SignalSpec, Vec<f32>)> {
+    loop {
+        let packet = format.next_packet().ok()?;
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let spec = *decoded.spec();
+                let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                buffer.copy_interleaved_ref(decoded);
+                return Some((spec, buffer.samples().to_vec()));
+            }
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(_) => return None,
+        }
+    }
+}
+
+impl Read for FilteredStream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if self.offset >= self.pending.len() && !self.fill() {
+            return Ok(0);
+        }
+
+        let count = buf.len().min(self.pending.len() - self.offset);
+        buf[..count].copy_from_slice(&self.pending[self.offset..self.offset + count]);
+        self.offset += count;
+        self.position += count as u64;
+
+        Ok(count)
+    }
+}
+
+impl Seek for FilteredStream {
+    fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
+        let target = match pos {
+            SeekFrom::Start(target) => target,
+            SeekFrom::Current(0) => return Ok(self.position),
+            _ => return Err(ErrorKind::Unsupported.into()),
+        };
+
+        // Within what was decoded last, such as the header while probing
+        let chunk_start = self.position - self.offset as u64;
+        if (chunk_start..=chunk_start + self.pending.len() as u64).contains(&target) {
+            self.offset = (target - chunk_start) as usize;
+            self.position = target;
+            return Ok(target);
+        }
+        if target < HEADER_LEN {
+            return Err(ErrorKind::Unsupported.into());
+        }
+
+        if self.seekable {
+            // Positions are in filtered time, the source is ahead of it when sped up
+            let frame = 4 * self.spec.channels.count() as u64;
+            let frames = (target - HEADER_LEN) / frame;
+            let seconds = frames as f64 / self.spec.rate as f64 * self.filters().speed as f64;
+
+            self.format
+                .seek(
+                    SeekMode::Accurate,
+                    SeekTo::Time {
+                        time: Time::from(seconds),
+                        track_id: Some(self.track_id),
+                    },
+                )
+                .map_err(IoError::other)?;
+            self.decoder.reset();
+            self.chain.reset();
+            self.pending.clear();
+            self.offset = 0;
+            self.position = HEADER_LEN + frames * frame;
+        } else if target >= self.position {
+            let mut dropped = [0; 8192];
+            while self.position < target {
+                let count = dropped.len().min((target - self.position) as usize);
+                if self.read(&mut dropped[..count])? == 0 {
+                    break;
+                }
+            }
+        } else {
+            return Err(ErrorKind::Unsupported.into());
+        }
+
+        Ok(self.position)
+    }
+}
+
+impl MediaSource for FilteredStream {
+    fn is_seekable(&self) -> bool {
+        self.seekable
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// The effects, in order: equaliser, karaoke, pitch, speed then rotation.
+struct Chain {
+    channels: usize,
+    rate: u32,
+    /// Filters the equaliser was designed for
+    designed: Filters,
+    equaliser: Vec<[Biquad; 3]>,
+    pitch: Vec<PitchShifter>,
+    resampler: Resampler,
+    /// Angle of the rotation, in turns
+    turn: f64,
+}
+
+impl Chain {
+    fn new(channels: usize, rate: u32) -> Self {
+        let designed = Filters::default();
+        Chain {
+            channels,
+            rate,
+            designed,
+            equaliser: (0..channels).map(|_| equaliser(&designed, rate)).collect(),
+            pitch: (0..channels).map(|_| PitchShifter::new()).collect(),
+            resampler: Resampler::default(),
+            turn: 0.0,
+        }
+    }
+
+    /// Drops the state carried from previous samples, after a seek.
+    fn reset(&mut self) {
+        *self = Chain {
+            turn: self.turn,
+            ..Chain::new(self.channels, self.rate)
+        };
+    }
+
+    fn process(&mut self, filters: Filters, input: &[f32], output: &mut Vec<f32>) {
+        if filters.is_flat() && self.designed.is_flat() {
+            output.extend_from_slice(input);
+            return;
+        }
+
+        let eq = |filters: &Filters| (filters.bass, filters.mid, filters.treble);
+        if eq(&filters) != eq(&self.designed) {
+            self.equaliser = (0..self.channels)
+                .map(|_| equaliser(&filters, self.rate))
+                .collect();
+        }
+        self.designed = filters;
+
+        let pitch = 2f64.powf(filters.pitch as f64 / 12.0);
+        let mut frames = Vec::with_capacity(input.len());
+
+        for frame in input.chunks_exact(self.channels) {
+            let start = frames.len();
+            for (channel, sample) in frame.iter().enumerate() {
+                let mut sample = *sample as f64;
+                for section in &mut self.equaliser[channel] {
+                    sample = section.process(sample);
+                }
+                frames.push(sample as f32);
+            }
+
+            let frame = &mut frames[start..];
+            if filters.karaoke && self.channels >= 2 {
+                let side = (frame[0] - frame[1]) / 2.0;
+                frame[0] = side;
+                frame[1] = side;
+            }
+            if pitch != 1.0 {
+                for (channel, sample) in frame.iter_mut().enumerate() {
+                    *sample = self.pitch[channel].process(*sample, pitch);
+                }
+            }
+        }
+
+        let start = output.len();
+        self.resampler
+            .process(&frames, self.channels, filters.speed as f64, output);
+
+        if filters.rotation != 0.0 && self.channels >= 2 {
+            let step = filters.rotation as f64 / self.rate as f64;
+            for frame in output[start..].chunks_exact_mut(self.channels) {
+                // Constant power panning, from the left all the way to the right and back. Never
+                // above unity, a normalised track would clip
+                let pan = (2.0 * PI * self.turn).sin();
+                let angle = (pan + 1.0) * PI / 4.0;
+                frame[0] *= angle.cos() as f32;
+                frame[1] *= angle.sin() as f32;
+                self.turn = (self.turn + step).fract();
+            }
+        }
+    }
+}
+
+/// Bass shelf, mid peak and treble shelf sections, as in the Audio EQ Cookbook.
+fn equaliser(filters: &Filters, rate: u32) -> [Biquad; 3] {
+    let rate = rate as f64;
+    let q = 1.0 / 2f64.sqrt();
+
+    let shelf = |frequency: f64, gain_db: f32, high: bool| {
+        let a = 10f64.powf(gain_db as f64 / 40.0);
+        let w0 = 2.0 * PI * frequency / rate;
+        let (sin, cos) = (w0.sin(), w0.cos());
+        let alpha = sin / (2.0 * q);
+        let root = 2.0 * a.sqrt() * alpha;
+        let sign = if high { -1.0 } else { 1.0 };
+
+        Biquad::new(
+            [
+                a * ((a + 1.0) - sign * (a - 1.0) * cos + root),
+                sign * 2.0 * a * ((a - 1.0) - sign * (a + 1.0) * cos),
+                a * ((a + 1.0) - sign * (a - 1.0) * cos - root),
+            ],
+            [
+                (a + 1.0) + sign * (a - 1.0) * cos + root,
+                -sign * 2.0 * ((a - 1.0) + sign * (a + 1.0) * cos),
+                (a + 1.0) + sign * (a - 1.0) * cos - root,
+            ],
+        )
+    };
+
+    let peak = |frequency: f64, gain_db: f32| {
+        let a = 10f64.powf(gain_db as f64 / 40.0);
+        let w0 = 2.0 * PI * frequency / rate;
+        let alpha = w0.sin() / (2.0 * q);
+
+        Biquad::new(
+            [1.0 + alpha * a, -2.0 * w0.cos(), 1.0 - alpha * a],
+            [1.0 + alpha / a, -2.0 * w0.cos(), 1.0 - alpha / a],
+        )
+    };
+
+    [
+        shelf(100.0, filters.bass, false),
+        peak(1000.0, filters.mid),
+        shelf(8000.0, filters.treble, true),
+    ]
+}
+
+/// Pitch shifter reading a delay line at another rate, with two taps half a window apart
+/// crossfaded to hide the jumps.
+struct PitchShifter {
+    buffer: Vec<f32>,
+    write: usize,
+    /// Delay of the first tap, in samples
+    delay: f64,
+}
+
+impl PitchShifter {
+    fn new() -> Self {
+        PitchShifter {
+            buffer: vec![0.0; PITCH_WINDOW + 2],
+            write: 0,
+            delay: 0.0,
+        }
+    }
+
+    fn process(&mut self, sample: f32, ratio: f64) -> f32 {
+        let window = PITCH_WINDOW as f64;
+        self.buffer[self.write] = sample;
+
+        let first = self.delay;
+        let second = (self.delay + window / 2.0) % window;
+        // sin² and cos² of the same angle, always summing to 1
+        let weight = |delay: f64| (PI * delay / window).sin().powi(2);
+        let output = self.tap(first) * weight(first) + self.tap(second) * weight(second);
+
+        // Reading `ratio` samples per written one shortens or lengthens the delay
+        self.delay = (self.delay + 1.0 - ratio).rem_euclid(window);
+        self.write = (self.write + 1) % self.buffer.len();
+
+        output as f32
+    }
+
+    /// Sample `delay` samples in the past, interpolated.
+    fn tap(&self, delay: f64) -> f64 {
+        let length = self.buffer.len();
+        let position = (self.write + length) as f64 - delay;
+        let index = position.floor() as usize;
+        let fraction = position.fract();
+
+        let current = self.buffer[index % length] as f64;
+        let next = self.buffer[(index + 1) % length] as f64;
+        current + (next - current) * fraction
+    }
+}
+
+/// Linear resampler playing interleaved frames at `speed` times their rate.
+#[derive(Default)]
+struct Resampler {
+    /// Frames not fully consumed yet
+    frames: Vec<f32>,
+    /// Read position in `frames`, in frames
+    phase: f64,
+}
+
+impl Resampler {
+    fn process(&mut self, input: &[f32], channels: usize, speed: f64, output: &mut Vec<f32>) {
+        if speed == 1.0 && self.frames.is_empty() {
+            output.extend_from_slice(input);
+            return;
+        }
+
+        self.frames.extend_from_slice(input);
+        let count = self.frames.len() / channels;
+        let speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+
+        while self.phase + 1.0 < count as f64 {
+            let index = self.phase as usize;
+            let fraction = (self.phase - index as f64) as f32;
+            for channel in 0..channels {
+                let current = self.frames[index * channels + channel];
+                let next = self.frames[(index + 1) * channels + channel];
+                output.push(current + (next - current) * fraction);
+            }
+            self.phase += speed;
+        }
+
+        let consumed = (self.phase as usize).min(count);
+        self.frames.drain(..consumed * channels);
+        self.phase -= consumed as f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f64, rate: u32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| (2.0 * PI * frequency * i as f64 / rate as f64).sin() as f32)
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f64 {
+        (samples
+            .iter()
+            .map(|sample| (*sample as f64).powi(2))
+            .sum::<f64>()
+            / samples.len() as f64)
+            .sqrt()
+    }
+
+    #[test]
+    fn flat_filters_pass_audio_through() {
+        let input = sine(440.0, 48_000, 4800);
+        let mut output = Vec::new();
+        Chain::new(1, 48_000).process(Filters::default(), &input, &mut output);
+
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn speed_changes_length() {
+        let input = sine(440.0, 48_000, 48_000);
+        let mut chain = Chain::new(1, 48_000);
+        let mut output = Vec::new();
+        for chunk in input.chunks(1024) {
+            chain.process(Preset::Nightcore.filters(), chunk, &mut output);
+        }
+
+        let expected = 48_000.0 / 1.25;
+        assert!(
+            (output.len() as f64 - expected).abs() < 2.0,
+            "{}",
+            output.len()
+        );
+    }
+
+    #[test]
+    fn bass_boost_lifts_low_frequencies_only() {
+        let boost = |frequency: f64| {
+            let input = sine(frequency, 48_000, 48_000);
+            let mut output = Vec::new();
+            Chain::new(1, 48_000).process(Preset::BassBoost.filters(), &input, &mut output);
+            20.0 * (rms(&output[24_000..]) / rms(&input[24_000..])).log10()
+        };
+
+        assert!((boost(40.0) - 8.0).abs() < 1.0, "{}", boost(40.0));
+        assert!(boost(5000.0).abs() < 0.5, "{}", boost(5000.0));
+    }
+
+    #[test]
+    fn karaoke_cancels_the_centre() {
+        let input: Vec<f32> = sine(440.0, 48_000, 4800)
+            .into_iter()
+            .flat_map(|sample| [sample, sample])
+            .collect();
+        let mut output = Vec::new();
+        Chain::new(2, 48_000).process(Preset::Karaoke.filters(), &input, &mut output);
+
+        assert!(rms(&output) < 1e-6);
+    }
+
+    #[test]
+    fn rotation_never_boosts() {
+        let input = vec![1.0; 48_000 * 2];
+        let filters = Filters {
+            rotation: 0.5,
+            ..Filters::default()
+        };
+        let mut output = Vec::new();
+        Chain::new(2, 48_000).process(filters, &input, &mut output);
+
+        assert!(output.iter().all(|sample| sample.abs() <= 1.0));
+    }
+
+    #[test]
+    fn describes_presets_and_manual_settings() {
+        assert_eq!(Filters::default().describe(), None);
+        assert_eq!(Preset::EightD.filters().describe().as_deref(), Some("8d"));
+
+        let manual = Filters {
+            pitch: 2.0,
+            treble: -3.0,
+            ..Filters::default()
+        };
+        assert_eq!(manual.describe().as_deref(), Some("pitch +2, treble -3dB"));
+    }
+}
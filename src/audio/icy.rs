@@ -0,0 +1,157 @@
+use crate::QueueKey;
+use crate::http::api::publish;
+use crate::messages::messages::update_message;
+use crate::settings::settings::settings;
+use reqwest::Client;
+use serenity::all::GuildId;
+use serenity::http::Http;
+use serenity::prelude::TypeMap;
+use songbird::tracks::{PlayMode, TrackHandle};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, info_span, warn};
+use tracing_futures::Instrument;
+
+/// How often a playing radio is asked for its current song.
+const POLL_PERIOD: Duration = Duration::from_secs(15);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Radios send metadata every few kilobytes, more than this is not a radio worth waiting for.
+const MAX_METADATA_INTERVAL: usize = 1 << 20;
+
+/// Keeps the title of a radio track on the song it plays, for as long as the track lives.
+///
+/// The played stream does not carry ICY metadata, which would corrupt the audio, so a separate
+/// request reads the first metadata block every [`POLL_PERIOD`] then hangs up.
+pub fn watch(
+    data: Arc<RwLock<TypeMap>>,
+    http: Arc<Http>,
+    guild_id: GuildId,
+    track: TrackHandle,
+    url: String,
+    client: Client,
+) {
+    tokio::spawn(
+        async move {
+            let mut last = None;
+
+            loop {
+                tokio::time::sleep(POLL_PERIOD).await;
+
+                // Ended tracks stop answering
+                let Ok(info) = track.get_info().await else {
+                    break;
+                };
+                if info.playing != PlayMode::Play {
+                    continue;
+                }
+
+                let title = now_playing(&client, &url).await;
+                if title.is_none() || title == last {
+                    continue;
+                }
+                debug!(?title, "Radio song changed");
+                last = title.clone();
+
+                set_title(&data, &http, guild_id, &url, title).await;
+            }
+        }
+        .instrument(info_span!("icy", guild_id = guild_id.get())),
+    );
+}
+
+/// Shows the song in place of the station name, on every entry of that radio.
+async fn set_title(
+    data: &Arc<RwLock<TypeMap>>,
+    http: &Http,
+    guild_id: GuildId,
+    url: &str,
+    title: Option<String>,
+) {
+    let settings = settings(data, guild_id).await;
+    let Some(queue_lock) = data.read().await.get::<QueueKey>().cloned() else {
+        return;
+    };
+    let mut maybe_queue = queue_lock.write().await;
+    let Some(queue) = maybe_queue.get_mut(&guild_id) else {
+        return;
+    };
+
+    for entry in queue.queue.iter_mut() {
+        if entry.metadata.source_url.as_deref() == Some(url) {
+            entry.metadata.title = title.clone();
+        }
+    }
+
+    update_message(http, queue, &settings)
+        .await
+        .map_err(|error| warn!(?error, "Failed to edit queue message"))
+        .unwrap_or_default();
+    publish(data, guild_id, queue).await;
+}
+
+/// Song a radio currently plays, from its `StreamTitle` metadata.
+pub async fn now_playing(client: &Client, url: &str) -> Option<String> {
+    let mut response = client
+        .get(url)
+        .header("Icy-MetaData", "1")
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .ok()?;
+    let interval = response
+        .headers()
+        .get("icy-metaint")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|interval| *interval <= MAX_METADATA_INTERVAL)?;
+
+    // The metadata block follows the first `interval` bytes of audio, prefixed by its length
+    // in blocks of 16 bytes
+    let mut received = Vec::with_capacity(interval + 1);
+    let mut end = interval + 1;
+    while received.len() < end {
+        let chunk = response.chunk().await.ok()??;
+        received.extend_from_slice(&chunk);
+
+        if end == interval + 1 && received.len() > interval {
+            end += received[interval] as usize * 16;
+        }
+    }
+
+    parse_title(&received[interval + 1..end])
+}
+
+/// Reads `StreamTitle='<title>';` out of a metadata block.
+fn parse_title(block: &[u8]) -> Option<String> {
+    let block = String::from_utf8_lossy(block);
+    let start = block.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &block[start..];
+    let title = rest.find("';").map(|end| &rest[..end]).unwrap_or(rest);
+    let title = title.trim_end_matches(['\0', '\'', ';']).trim();
+
+    Some(String::from(title)).filter(|title| !title.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_stream_titles() {
+        assert_eq!(
+            parse_title(b"StreamTitle='Daft Punk - Something About Us';StreamUrl='';\0\0\0")
+                .as_deref(),
+            Some("Daft Punk - Something About Us")
+        );
+        assert_eq!(
+            parse_title(b"StreamTitle='It's Over';\0").as_deref(),
+            Some("It's Over")
+        );
+        assert_eq!(parse_title(b"StreamTitle='';\0\0"), None);
+        assert_eq!(parse_title(b"\0\0\0\0"), None);
+    }
+}
@@ -0,0 +1,335 @@
+use crate::audio::source::Source;
+use crate::settings::settings::Settings;
+use serenity::prelude::{TypeMap, TypeMapKey};
+use songbird::input::codecs::{get_codec_registry, get_probe};
+use songbird::input::{Compose, Input};
+use songbird::tracks::{Track, TrackHandle};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tokio::sync::{RwLock, Semaphore};
+use tracing::{debug, info_span, warn};
+use tracing_futures::Instrument;
+
+/// Audio scanned to estimate the loudness of a track, from its start.
+const SCAN_SECONDS: usize = 20;
+/// Gains are bounded so a quiet intro does not blow a track up, in dB.
+const MIN_GAIN_DB: f64 = -20.0;
+const MAX_GAIN_DB: f64 = 10.0;
+/// Scans running at once, each one downloading and decoding its track.
+const CONCURRENT_SCANS: usize = 2;
+
+/// Normalisation gains already computed, by source URL.
+pub struct LoudnessKey;
+
+impl TypeMapKey for LoudnessKey {
+    type Value = Arc<RwLock<HashMap<String, f32>>>;
+}
+
+/// Limits the scans running at once, queued in the order tracks were enqueued.
+pub struct ScanPermitsKey;
+
+impl TypeMapKey for ScanPermitsKey {
+    type Value = Arc<Semaphore>;
+}
+
+/// Permits shared by every guild, see [`ScanPermitsKey`].
+pub fn scan_permits() -> Arc<Semaphore> {
+    Arc::new(Semaphore::new(CONCURRENT_SCANS))
+}
+
+/// Normalisation gain of a track, attached to it so volume changes can keep it.
+pub struct TrackGain(AtomicU32);
+
+impl TrackGain {
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, gain: f32) {
+        self.0.store(gain.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Builds a Songbird track at the guild volume, carrying a neutral gain until it is scanned.
+/// Every track of the queue goes through here, as [`gain`] expects the attached [`TrackGain`].
+pub fn track(input: Input, settings: &Settings) -> Track {
+    Track::new_with_data(input, Arc::new(TrackGain(AtomicU32::new(1f32.to_bits()))))
+        .volume(settings.volume())
+}
+
+/// Normalisation gain of an enqueued track, `1.0` until it is known.
+pub fn gain(track: &TrackHandle) -> f32 {
+    track.data::<TrackGain>().get()
+}
+
+/// Applies the normalisation gain of `url` to an enqueued track, scanning it in the background
+/// unless it is cached. Does nothing when normalisation is disabled for the guild.
+pub fn normalize(
+    data: Arc<RwLock<TypeMap>>,
+    settings: &Settings,
+    url: String,
+    src: Source,
+    track: TrackHandle,
+) {
+    if !settings.normalize {
+        return;
+    }
+
+    let target = settings.target_lufs as f64;
+    let span = info_span!("loudness", %url);
+
+    tokio::spawn(
+        async move {
+            let (cache, permits) = {
+                let guard = data.read().await;
+                (
+                    guard.get::<LoudnessKey>().cloned(),
+                    guard.get::<ScanPermitsKey>().cloned(),
+                )
+            };
+
+            let cached = match &cache {
+                Some(cache) => cache.read().await.get(&url).copied(),
+                None => None,
+            };
+
+            let track_gain = match cached {
+                Some(track_gain) => track_gain,
+                None => {
+                    let _permit = match &permits {
+                        Some(permits) => permits.acquire().await.ok(),
+                        None => None,
+                    };
+                    // The track may have been skipped or removed while waiting
+                    if track.get_info().await.is_err() {
+                        return;
+                    }
+
+                    let Some(loudness) = scan(src).await else {
+                        warn!("Could not measure loudness, leaving the track as is");
+                        return;
+                    };
+
+                    let gain = gain_for(loudness, target);
+                    debug!(loudness, gain, "Measured loudness");
+
+                    if let Some(cache) = &cache {
+                        cache.write().await.insert(url, gain);
+                    }
+                    gain
+                }
+            };
+
+            // Keep the volume, which may have changed while scanning
+            let previous_gain = gain(&track);
+            track.data::<TrackGain>().set(track_gain);
+            if let Ok(info) = track.get_info().await {
+                track
+                    .set_volume(info.volume / previous_gain * track_gain)
+                    .unwrap_or_default();
+            }
+        }
+        .instrument(span),
+    );
+}
+
+/// Linear gain bringing `loudness` to `target`, both in LUFS.
+fn gain_for(loudness: f64, target: f64) -> f32 {
+    10f64.powf((target - loudness).clamp(MIN_GAIN_DB, MAX_GAIN_DB) / 20.0) as f32
+}
+
+/// Decodes the start of a track and measures its integrated loudness.
+async fn scan(mut src: Source) -> Option<f64> {
+    let stream = src.create_async().await.ok()?;
+
+    tokio::task::spawn_blocking(move || {
+        let hint = stream.hint.unwrap_or_else(Hint::new);
+        let source = MediaSourceStream::new(stream.input, Default::default());
+        let probed = get_probe()
+            .format(
+                &hint,
+                source,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .ok()?;
+        let mut format = probed.format;
+
+        let track = format.default_track()?;
+        let track_id = track.id;
+        let mut decoder = get_codec_registry()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .ok()?;
+
+        let mut samples: Vec<f32> = Vec::new();
+        let mut spec = None;
+
+        while let Ok(packet) = format.next_packet() {
+            if packet.track_id() != track_id {
+                continue;
+            }
+            let Ok(decoded) = decoder.decode(&packet) else {
+                continue;
+            };
+
+            let decoded_spec = *decoded.spec();
+            let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, decoded_spec);
+            buffer.copy_interleaved_ref(decoded);
+            samples.extend_from_slice(buffer.samples());
+            spec = Some(decoded_spec);
+
+            let channels = decoded_spec.channels.count();
+            if samples.len() >= SCAN_SECONDS * decoded_spec.rate as usize * channels {
+                break;
+            }
+        }
+
+        let spec = spec?;
+        integrated_loudness(&samples, spec.channels.count(), spec.rate)
+    })
+    .await
+    .ok()?
+}
+
+/// Second-order IIR filter section.
+pub(crate) struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    /// Section from raw coefficients, normalised so that `a[0]` is 1.
+    pub(crate) fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Biquad {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [1.0, a[1] / a[0], a[2] / a[0]],
+            z: [0.0; 2],
+        }
+    }
+
+    pub(crate) fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[1] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[2] * y;
+        y
+    }
+}
+
+/// K-weighting of ITU-R BS.1770: a high shelf modelling the head, then a high pass.
+fn k_weighting(rate: u32) -> [Biquad; 2] {
+    let rate = rate as f64;
+
+    let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    [shelf, high_pass]
+}
+
+/// Gated integrated loudness of interleaved samples in LUFS, following ITU-R BS.1770 with every
+/// channel weighted equally. `None` for silence or less than one 400ms block.
+fn integrated_loudness(samples: &[f32], channels: usize, rate: u32) -> Option<f64> {
+    if channels == 0 || rate == 0 {
+        return None;
+    }
+
+    let mut filters: Vec<[Biquad; 2]> = (0..channels).map(|_| k_weighting(rate)).collect();
+
+    // Energy of every 100ms step, blocks are 400ms with 75% overlap
+    let step = rate as usize / 10;
+    let mut steps = Vec::new();
+    let mut energy = 0.0;
+    for (i, frame) in samples.chunks_exact(channels).enumerate() {
+        for (channel, sample) in frame.iter().enumerate() {
+            let [shelf, high_pass] = &mut filters[channel];
+            let weighted = high_pass.process(shelf.process(*sample as f64));
+            energy += weighted * weighted;
+        }
+        if (i + 1) % step == 0 {
+            steps.push(energy / step as f64);
+            energy = 0.0;
+        }
+    }
+
+    let blocks: Vec<f64> = steps
+        .windows(4)
+        .map(|window| window.iter().sum::<f64>() / 4.0)
+        .collect();
+
+    let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+    let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+
+    let absolute: Vec<f64> = blocks
+        .into_iter()
+        .filter(|power| *power > 0.0 && loudness(*power) > -70.0)
+        .collect();
+    if absolute.is_empty() {
+        return None;
+    }
+
+    let relative_gate = loudness(mean(&absolute)) - 10.0;
+    let relative: Vec<f64> = absolute
+        .into_iter()
+        .filter(|power| loudness(*power) > relative_gate)
+        .collect();
+
+    Some(loudness(mean(&relative)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32, rate: u32, seconds: usize) -> Vec<f32> {
+        (0..rate as usize * seconds)
+            .map(|i| amplitude * (2.0 * PI * 997.0 * i as f64 / rate as f64).sin() as f32)
+            .collect()
+    }
+
+    #[test]
+    fn measures_reference_sine() {
+        // BS.1770 calibration: a full scale 997Hz sine on one channel reads -3.01 LKFS
+        let full = integrated_loudness(&sine(1.0, 48_000, 5), 1, 48_000).unwrap();
+        let quiet = integrated_loudness(&sine(0.1, 44_100, 5), 1, 44_100).unwrap();
+
+        assert!((full + 3.01).abs() < 0.1, "{}", full);
+        assert!((quiet + 23.01).abs() < 0.1, "{}", quiet);
+        assert_eq!(integrated_loudness(&[0.0; 48_000], 1, 48_000), None);
+    }
+
+    #[test]
+    fn bounds_gain() {
+        assert!((gain_for(-14.0, -14.0) - 1.0).abs() < f32::EPSILON);
+        assert!((gain_for(-8.0, -14.0) - 0.5012).abs() < 0.001);
+        assert!((gain_for(-60.0, -14.0) - 10f32.powf(0.5)).abs() < 0.001);
+    }
+}
@@ -0,0 +1,6 @@
+pub(crate) mod loudness;
+pub(crate) mod filters;
+pub(crate) mod crossfade;
+pub(crate) mod source;
+pub(crate) mod direct;
+pub(crate) mod icy;
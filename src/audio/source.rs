@@ -0,0 +1,119 @@
+use serenity::async_trait;
+use songbird::input::codecs::get_probe;
+use songbird::input::{
+    AudioStream, AudioStreamError, AuxMetadata, Compose, File, HttpRequest, YoutubeDl,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+
+/// Where the audio of a track is read from: through yt-dlp, straight from an audio link, or from
+/// a file of the library.
+#[derive(Clone)]
+pub enum Source {
+    YoutubeDl(YoutubeDl<'static>),
+    Http(HttpRequest),
+    File(File<PathBuf>),
+}
+
+#[async_trait]
+impl Compose for Source {
+    fn create(&mut self) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        match self {
+            Source::YoutubeDl(src) => src.create(),
+            Source::Http(src) => src.create(),
+            Source::File(src) => src.create(),
+        }
+    }
+
+    async fn create_async(
+        &mut self,
+    ) -> Result<AudioStream<Box<dyn MediaSource>>, AudioStreamError> {
+        match self {
+            Source::YoutubeDl(src) => src.create_async().await,
+            Source::Http(src) => src.create_async().await,
+            Source::File(src) => src.create_async().await,
+        }
+    }
+
+    fn should_create_async(&self) -> bool {
+        match self {
+            Source::YoutubeDl(src) => src.should_create_async(),
+            Source::Http(src) => src.should_create_async(),
+            Source::File(src) => src.should_create_async(),
+        }
+    }
+
+    async fn aux_metadata(&mut self) -> Result<AuxMetadata, AudioStreamError> {
+        match self {
+            Source::YoutubeDl(src) => src.aux_metadata().await,
+            Source::Http(src) => src.aux_metadata().await,
+            Source::File(src) => src.aux_metadata().await,
+        }
+    }
+}
+
+/// Reads the tags and the duration of an audio file from its start. The duration is only known
+/// when the container tells it, streams have none.
+pub async fn describe(src: &mut (impl Compose + ?Sized)) -> Option<AuxMetadata> {
+    let stream = src.create_async().await.ok()?;
+
+    // Probing reads the start of the stream, which blocks
+    tokio::task::spawn_blocking(move || {
+        let hint = stream.hint.unwrap_or_default();
+        let source = MediaSourceStream::new(stream.input, Default::default());
+        let mut probed = get_probe()
+            .format(
+                &hint,
+                source,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .ok()?;
+
+        let mut metadata = AuxMetadata::default();
+
+        // Tags found before the container, such as ID3, then the container's own
+        if let Some(revision) = probed.metadata.get().as_ref().and_then(|log| log.current()) {
+            read_tags(revision, &mut metadata);
+        }
+        if let Some(revision) = probed.format.metadata().current() {
+            read_tags(revision, &mut metadata);
+        }
+
+        let track = probed.format.default_track()?;
+        let params = &track.codec_params;
+        metadata.sample_rate = params.sample_rate;
+        metadata.channels = params.channels.map(|channels| channels.count() as u8);
+        metadata.duration = match (params.n_frames, params.time_base, params.sample_rate) {
+            (Some(frames), Some(time_base), _) => {
+                let time = time_base.calc_time(frames);
+                Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+            }
+            (Some(frames), None, Some(rate)) => {
+                Some(Duration::from_secs_f64(frames as f64 / rate as f64))
+            }
+            _ => None,
+        };
+
+        Some(metadata)
+    })
+    .await
+    .ok()?
+}
+
+fn read_tags(revision: &MetadataRevision, metadata: &mut AuxMetadata) {
+    for tag in revision.tags() {
+        let value = Some(tag.value.to_string()).filter(|value| !value.trim().is_empty());
+
+        match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => metadata.title = value.or(metadata.title.take()),
+            Some(StandardTagKey::Artist) => metadata.artist = value.or(metadata.artist.take()),
+            Some(StandardTagKey::Album) => metadata.album = value.or(metadata.album.take()),
+            Some(StandardTagKey::Date) => metadata.date = value.or(metadata.date.take()),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use crate::QueueKey;
+use crate::commands::search::ytdl_search;
+use crate::errors::errors::BeatError;
+use crate::history::history::entries;
+use crate::messages::messages::{track_artist, track_title, truncate};
+use crate::settings::settings::settings;
+use serde_json::json;
+use serenity::all::{GuildId, Interaction, UserId};
+use serenity::client::Context;
+use serenity::json::Value;
+use serenity::prelude::{TypeMap, TypeMapKey};
+use songbird::input::AuxMetadata;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+use tracing::debug;
+
+/// Discord shows at most this many choices.
+const MAX_CHOICES: usize = 25;
+/// Choice names and values are capped by Discord.
+const MAX_CHOICE_LENGTH: usize = 100;
+/// Time without a new keystroke before searching YouTube.
+const DEBOUNCE: Duration = Duration::from_millis(400);
+/// Discord drops suggestions answered after 3 seconds.
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(2);
+const SEARCH_RESULTS: usize = 5;
+const MIN_SEARCH_LENGTH: usize = 3;
+const CACHE_TTL: Duration = Duration::from_secs(600);
+const CACHE_CAPACITY: usize = 500;
+
+/// State of the YouTube suggestions: searches already run, and the latest keystroke of each
+/// user so that older ones give up.
+pub struct SuggestionsKey;
+
+impl TypeMapKey for SuggestionsKey {
+    type Value = Arc<Mutex<Suggestions>>;
+}
+
+#[derive(Default)]
+pub struct Suggestions {
+    cache: HashMap<String, (Instant, Vec<AuxMetadata>)>,
+    keystrokes: HashMap<UserId, u64>,
+}
+
+/// Suggests tracks for the `track` option of `/play` and `/playnext`, from the session, the play
+/// history, the saved playlists and, when the guild enabled it, a YouTube search. Every
+/// suggestion is a link so that picking one plays exactly that track.
+pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatError> {
+    if let Interaction::Autocomplete(autocomplete) = interaction {
+        let guild_id = autocomplete.guild_id.ok_or(BeatError::NoGuild)?;
+        let Some(focused) = autocomplete.data.autocomplete() else {
+            return Ok(());
+        };
+        if focused.name != "track" {
+            return Ok(());
+        }
+        let query = focused.value.trim();
+
+        let mut known = recent_tracks(&ctx.data, guild_id).await;
+        known.extend(played_tracks(guild_id));
+        known.extend(playlist_entries(guild_id, &known));
+
+        let found = if settings(&ctx.data, guild_id).await.search_suggestions
+            && query.chars().count() >= MIN_SEARCH_LENGTH
+            && !query.starts_with("http")
+        {
+            search(&ctx.data, autocomplete.user.id, query)
+                .await
+                .iter()
+                .filter_map(|track| Some((label(track), track.source_url.clone()?)))
+                .collect()
+        } else {
+            vec![]
+        };
+
+        ctx.http
+            .create_interaction_response(
+                autocomplete.id,
+                &autocomplete.token,
+                &json!({"type": 8, "data": {"choices": choices(query, known, found)}}),
+                vec![],
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn label(track: &AuxMetadata) -> String {
+    format!("{} - {}", track_title(track), track_artist(track))
+}
+
+/// Tracks of the current session, latest first.
+async fn recent_tracks(data: &Arc<RwLock<TypeMap>>, guild_id: GuildId) -> Vec<(String, String)> {
+    let Some(queue_lock) = data.read().await.get::<QueueKey>().cloned() else {
+        return vec![];
+    };
+    let maybe_queue = queue_lock.read().await;
+
+    maybe_queue
+        .get(&guild_id)
+        .map(|queue| {
+            queue
+                .queue
+                .iter()
+                .rev()
+                .filter_map(|entry| {
+                    Some((label(&entry.metadata), entry.metadata.source_url.clone()?))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Tracks of the play history, latest first.
+fn played_tracks(guild_id: GuildId) -> Vec<(String, String)> {
+    entries(guild_id)
+        .into_iter()
+        .rev()
+        .map(|entry| {
+            let label = format!(
+                "{} - {}",
+                entry.title.as_deref().unwrap_or(&entry.url),
+                entry.artist.as_deref().unwrap_or("Unknown artist")
+            );
+            (label, entry.url)
+        })
+        .collect()
+}
+
+/// Links of the saved playlists, named after the track when the session knows it.
+fn playlist_entries(guild_id: GuildId, known: &[(String, String)]) -> Vec<(String, String)> {
+    let Ok(entries) = fs::read_dir(format!("./{}", guild_id)) else {
+        return vec![];
+    };
+
+    let titles: HashMap<&str, &str> = known
+        .iter()
+        .map(|(label, url)| (url.as_str(), label.as_str()))
+        .collect();
+
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "playlist")
+        })
+        .flat_map(|path| {
+            let name = path
+                .file_stem()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            fs::read_to_string(&path)
+                .unwrap_or_default()
+                .lines()
+                .filter(|url| !url.is_empty())
+                .map(|url| {
+                    let title = titles.get(url).copied().unwrap_or(url);
+                    (format!("{} · {}", name, title), String::from(url))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// YouTube results for a query, once the user stopped typing. Results are cached, and a
+/// keystroke superseded while waiting gets nothing since Discord only shows the latest answer.
+async fn search(data: &Arc<RwLock<TypeMap>>, user_id: UserId, query: &str) -> Vec<AuxMetadata> {
+    let Some(suggestions) = data.read().await.get::<SuggestionsKey>().cloned() else {
+        return vec![];
+    };
+    let key = query.to_lowercase();
+
+    let keystroke = {
+        let mut suggestions = suggestions.lock().await;
+        if let Some((at, results)) = suggestions.cache.get(&key)
+            && at.elapsed() < CACHE_TTL
+        {
+            return results.clone();
+        }
+
+        let keystroke = suggestions.keystrokes.entry(user_id).or_default();
+        *keystroke += 1;
+        *keystroke
+    };
+
+    tokio::time::sleep(DEBOUNCE).await;
+    if suggestions.lock().await.keystrokes.get(&user_id) != Some(&keystroke) {
+        return vec![];
+    }
+
+    debug!(%query, "Searching suggestions");
+    let Ok(Some(results)) =
+        tokio::time::timeout(SEARCH_TIMEOUT, ytdl_search(query, SEARCH_RESULTS)).await
+    else {
+        return vec![];
+    };
+
+    let mut suggestions = suggestions.lock().await;
+    suggestions
+        .cache
+        .retain(|_, (at, _)| at.elapsed() < CACHE_TTL);
+    if suggestions.cache.len() < CACHE_CAPACITY {
+        suggestions
+            .cache
+            .insert(key, (Instant::now(), results.clone()));
+    }
+
+    results
+}
+
+/// Known tracks matching what was typed so far, then the search results which YouTube already
+/// matched, without duplicates nor links Discord would reject.
+fn choices(query: &str, known: Vec<(String, String)>, found: Vec<(String, String)>) -> Vec<Value> {
+    let query = query.to_lowercase();
+    let mut seen = HashSet::new();
+
+    let known = known
+        .into_iter()
+        .filter(|(label, url)| {
+            label.to_lowercase().contains(&query) || url.to_lowercase().contains(&query)
+        })
+        .take(MAX_CHOICES.saturating_sub(found.len()));
+
+    known
+        .chain(found)
+        .filter(|(_, url)| url.len() <= MAX_CHOICE_LENGTH && seen.insert(url.clone()))
+        .take(MAX_CHOICES)
+        .map(|(label, url)| json!({"name": truncate(&label, MAX_CHOICE_LENGTH), "value": url}))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(label: &str, url: &str) -> (String, String) {
+        (String::from(label), String::from(url))
+    }
+
+    #[test]
+    fn suggests_matching_links_once() {
+        let known = vec![
+            track(
+                "Never Gonna Give You Up - Rick Astley",
+                "https://youtu.be/a",
+            ),
+            track("Take On Me - a-ha", "https://youtu.be/b"),
+            track(
+                "party · Never Gonna Give You Up - Rick Astley",
+                "https://youtu.be/a",
+            ),
+            track(
+                "Rick Roll",
+                &format!("https://youtu.be/{}", "x".repeat(100)),
+            ),
+        ];
+        let found = vec![track(
+            "Together Forever - Rick Astley",
+            "https://youtu.be/c",
+        )];
+
+        let choices = choices("RICK", known, found);
+        let values: Vec<&str> = choices
+            .iter()
+            .filter_map(|choice| choice["value"].as_str())
+            .collect();
+        assert_eq!(values, ["https://youtu.be/a", "https://youtu.be/c"]);
+    }
+
+    #[test]
+    fn keeps_room_for_search_results() {
+        let known = (0..30)
+            .map(|i| track(&format!("Track {}", i), &format!("https://youtu.be/{}", i)))
+            .collect();
+        let found = vec![track("Found", "https://youtu.be/found")];
+
+        let choices = choices("", known, found);
+        assert_eq!(choices.len(), MAX_CHOICES);
+        assert_eq!(choices[MAX_CHOICES - 1]["value"], "https://youtu.be/found");
+    }
+}
@@ -0,0 +1,197 @@
+use crate::commands::play::{TrackRequest, enqueue, songbird_manager, ytdl_playlist};
+use crate::errors::errors::BeatError;
+use crate::history::history::entries;
+use crate::settings::settings::Settings;
+use crate::{HttpKey, Queue, QueueKey, TrackSource};
+use serenity::all::{ChannelId, GuildId};
+use serenity::http::Http;
+use serenity::prelude::TypeMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, info, info_span, warn};
+use tracing_futures::Instrument;
+use url::Url;
+
+/// Queues a track related to the last one of the queue, when the guild enabled autoplay and
+/// listeners did something within the last `autoplay_limit` autoplayed tracks. Called as the
+/// last track starts, so the related one loads like any upcoming track.
+pub fn queue_related(
+    data: Arc<RwLock<TypeMap>>,
+    http: Arc<Http>,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    queue: &mut Queue,
+    settings: &Settings,
+) {
+    if !settings.autoplay || queue.autoplaying || !queue.is_last() {
+        return;
+    }
+    if queue.autoplayed >= settings.autoplay_limit {
+        info!(
+            autoplayed = queue.autoplayed,
+            "Nobody is listening, autoplay stops"
+        );
+        return;
+    }
+    let seed = queue
+        .queue
+        .get(queue.playing_index)
+        .and_then(|entry| entry.metadata.source_url.clone());
+
+    let played: HashSet<String> = queue
+        .queue
+        .iter()
+        .filter_map(|entry| entry.metadata.source_url.clone())
+        .collect();
+    queue.autoplaying = true;
+    queue.autoplayed += 1;
+    let picking = Picking {
+        data: data.clone(),
+        guild_id,
+    };
+
+    tokio::spawn(
+        async move {
+            let _picking = picking;
+
+            if let Some(url) = related(seed.as_deref(), &played, guild_id).await {
+                debug!(%url, "Autoplaying");
+                autoplay(&data, &http, guild_id, channel_id, url)
+                    .await
+                    .map_err(|error| warn!(%error, "Failed to autoplay"))
+                    .unwrap_or_default();
+            }
+        }
+        .instrument(info_span!("autoplay", guild_id = guild_id.get())),
+    );
+}
+
+/// Clears `autoplaying` once a pick is over, however it ends, so that a failed one does not
+/// leave autoplay off for the rest of the session.
+struct Picking {
+    data: Arc<RwLock<TypeMap>>,
+    guild_id: GuildId,
+}
+
+impl Drop for Picking {
+    fn drop(&mut self) {
+        let data = self.data.clone();
+        let guild_id = self.guild_id;
+
+        tokio::spawn(async move {
+            if let Some(queue_lock) = data.read().await.get::<QueueKey>().cloned()
+                && let Some(queue) = queue_lock.write().await.get_mut(&guild_id)
+            {
+                queue.autoplaying = false;
+            }
+        });
+    }
+}
+
+/// Gives autoplay a fresh start once a listener interacts with the guild.
+pub async fn listener_acted(data: &Arc<RwLock<TypeMap>>, guild_id: GuildId) {
+    if let Some(queue_lock) = data.read().await.get::<QueueKey>().cloned()
+        && let Some(queue) = queue_lock.write().await.get_mut(&guild_id)
+    {
+        queue.autoplayed = 0;
+    }
+}
+
+async fn autoplay(
+    data: &Arc<RwLock<TypeMap>>,
+    http: &Arc<Http>,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    url: String,
+) -> Result<(), BeatError> {
+    let manager = songbird_manager(data).await?;
+    let handler_lock = manager.get(guild_id).ok_or(BeatError::NoManager)?;
+    let http_client = {
+        let guard = data.read().await;
+        guard.get::<HttpKey>().cloned().ok_or(BeatError::NoHttp)?
+    };
+
+    let request = TrackRequest {
+        guild_id,
+        channel_id,
+        url,
+        requester: None,
+        source: TrackSource::Autoplay,
+        position: None,
+    };
+
+    enqueue(data, http, handler_lock, request, http_client).await
+}
+
+/// A track not played this session, from the YouTube Mix of `seed` or else from the history.
+async fn related(
+    seed: Option<&str>,
+    played: &HashSet<String>,
+    guild_id: GuildId,
+) -> Option<String> {
+    if let Some(mix) = seed.and_then(mix_url)
+        && let Some((_, tracks)) = ytdl_playlist(mix).await
+        && let Some(track) = pick(tracks, played)
+    {
+        return Some(track);
+    }
+
+    pick(
+        entries(guild_id).into_iter().rev().map(|entry| entry.url),
+        played,
+    )
+}
+
+fn pick(candidates: impl IntoIterator<Item = String>, played: &HashSet<String>) -> Option<String> {
+    candidates
+        .into_iter()
+        .find(|candidate| !played.contains(candidate))
+}
+
+/// YouTube Mix of a video, a playlist of related tracks starting with the video itself.
+fn mix_url(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let id = match url.host_str()? {
+        "youtu.be" => url.path_segments()?.next().map(String::from),
+        host if host == "youtube.com" || host.ends_with(".youtube.com") => url
+            .query_pairs()
+            .find(|(key, _)| key == "v")
+            .map(|(_, id)| id.into_owned()),
+        _ => None,
+    }
+    .filter(|id| !id.is_empty())?;
+
+    Some(format!(
+        "https://www.youtube.com/watch?v={}&list=RD{}",
+        id, id
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_mixes_of_youtube_videos() {
+        assert_eq!(
+            mix_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=43").as_deref(),
+            Some("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=RDdQw4w9WgXcQ")
+        );
+        assert_eq!(
+            mix_url("https://youtu.be/dQw4w9WgXcQ").as_deref(),
+            Some("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=RDdQw4w9WgXcQ")
+        );
+        assert_eq!(mix_url("https://soundcloud.com/artist/track"), None);
+        assert_eq!(mix_url("https://www.youtube.com/playlist?list=PL123"), None);
+    }
+
+    #[test]
+    fn picks_a_track_not_played_yet() {
+        let played = HashSet::from([String::from("a"), String::from("b")]);
+        let candidates = ["a", "b", "c", "d"].map(String::from);
+
+        assert_eq!(pick(candidates, &played).as_deref(), Some("c"));
+        assert_eq!(pick([String::from("a")], &played), None);
+    }
+}
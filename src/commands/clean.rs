@@ -1,13 +1,15 @@
 use crate::QueueKey;
 use crate::errors::errors::BeatError;
 use serde_json::json;
-use serenity::all::Interaction;
+use serenity::all::{Interaction, Permissions};
 use serenity::builder::CreateCommand;
 use serenity::client::Context;
 use tracing::error;
 
 pub fn register() -> CreateCommand {
-    CreateCommand::new("clean").description("Removes all previous messages from the bot")
+    CreateCommand::new("clean")
+        .description("Removes all previous messages from the bot")
+        .default_member_permissions(Permissions::MANAGE_GUILD)
 }
 
 pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatError> {
@@ -0,0 +1,58 @@
+use crate::errors::errors::BeatError;
+use serenity::all::{
+    CommandOptionType, Context, CreateCommand, CreateCommandOption, Interaction, Permissions,
+    ResolvedOption, ResolvedValue,
+};
+use std::fs;
+use std::io::ErrorKind;
+use tracing::debug;
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("delete")
+        .description("Deletes a playlist saved with /save")
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "name",
+                "The name of the playlist",
+            )
+            .required(true)
+            .max_length(100)
+            .min_length(1),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &Interaction,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BeatError> {
+    if let Some(ResolvedOption {
+        value: ResolvedValue::String(name),
+        ..
+    }) = options.first()
+        && let Interaction::Command(command) = interaction
+    {
+        command.defer_ephemeral(ctx).await?;
+        let guild_id = command.guild_id.ok_or(BeatError::NoGuild)?;
+
+        // Only the playlists of the guild, not any file the name leads to
+        if name.contains(['/', '\\']) || name.contains("..") {
+            return Err(BeatError::Other("Not a valid playlist name"));
+        }
+
+        debug!(%name, "Deleting playlist");
+        fs::remove_file(format!("./{}/{}.playlist", guild_id, name)).map_err(
+            |error| match error.kind() {
+                ErrorKind::NotFound => BeatError::Other("No playlist with that name"),
+                _ => BeatError::from(error),
+            },
+        )?;
+
+        // Delete ephemeral response
+        command.delete_response(ctx).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use crate::QueueKey;
+use crate::audio::filters::{Filters, MAX_GAIN_DB, MAX_PITCH, MAX_SPEED, MIN_SPEED, Preset};
+use crate::errors::errors::BeatError;
+use crate::http::api::publish;
+use crate::messages::messages::update_message;
+use crate::settings::settings::settings;
+use serenity::all::{GuildId, Interaction};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::client::Context;
+use serenity::http::Http;
+use serenity::model::application::{CommandOptionType, ResolvedOption, ResolvedValue};
+use serenity::prelude::TypeMap;
+use std::sync::{Arc, PoisonError};
+use tokio::sync::RwLock;
+
+pub fn register() -> CreateCommand {
+    let subcommand = |name: &str, description: &str| {
+        CreateCommandOption::new(CommandOptionType::SubCommand, name, description)
+    };
+    let number = |name: &str, description: &str, min: f64, max: f64| {
+        CreateCommandOption::new(CommandOptionType::Number, name, description)
+            .min_number_value(min)
+            .max_number_value(max)
+    };
+
+    let presets = Preset::ALL.into_iter().fold(
+        CreateCommandOption::new(CommandOptionType::String, "name", "The preset").required(true),
+        |option, preset| option.add_string_choice(preset.name(), preset.name()),
+    );
+
+    CreateCommand::new("filter")
+        .description("Applies audio effects to the current and upcoming tracks")
+        .add_option(
+            subcommand("preset", "Replaces the effects with a preset").add_sub_option(presets),
+        )
+        .add_option(
+            subcommand("speed", "Plays faster or slower, changing the pitch too").add_sub_option(
+                number("rate", "1 for normal speed", MIN_SPEED, MAX_SPEED).required(true),
+            ),
+        )
+        .add_option(
+            subcommand("pitch", "Shifts the pitch, keeping the tempo").add_sub_option(
+                number(
+                    "semitones",
+                    "0 for the original pitch",
+                    -MAX_PITCH,
+                    MAX_PITCH,
+                )
+                .required(true),
+            ),
+        )
+        .add_option(
+            subcommand("eq", "Boosts or cuts frequency bands, in dB")
+                .add_sub_option(number("bass", "Around 100Hz", -MAX_GAIN_DB, MAX_GAIN_DB))
+                .add_sub_option(number("mid", "Around 1kHz", -MAX_GAIN_DB, MAX_GAIN_DB))
+                .add_sub_option(number("treble", "Around 8kHz", -MAX_GAIN_DB, MAX_GAIN_DB)),
+        )
+        .add_option(subcommand("reset", "Removes every effect"))
+}
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &Interaction,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BeatError> {
+    if let Interaction::Command(command) = interaction {
+        command.defer_ephemeral(ctx).await?;
+        let guild_id = command.guild_id.ok_or(BeatError::NoGuild)?;
+
+        let Some(ResolvedOption {
+            name,
+            value: ResolvedValue::SubCommand(arguments),
+            ..
+        }) = options.first()
+        else {
+            return Err(BeatError::NoValidCommand);
+        };
+
+        let number = |name: &str| {
+            arguments.iter().find_map(|option| match option.value {
+                ResolvedValue::Number(value) if option.name == name => Some(value as f32),
+                _ => None,
+            })
+        };
+
+        match *name {
+            "preset" => {
+                let preset = arguments
+                    .iter()
+                    .find_map(|option| match option.value {
+                        ResolvedValue::String(name) => Preset::from_name(name),
+                        _ => None,
+                    })
+                    .ok_or(BeatError::NoValidCommand)?;
+                set(&ctx.data, &ctx.http, guild_id, |filters| {
+                    *filters = preset.filters()
+                })
+                .await?
+            }
+            "speed" => {
+                let speed = number("rate").ok_or(BeatError::NoValidCommand)?;
+                set(&ctx.data, &ctx.http, guild_id, |filters| {
+                    filters.speed = speed
+                })
+                .await?
+            }
+            "pitch" => {
+                let pitch = number("semitones").ok_or(BeatError::NoValidCommand)?;
+                set(&ctx.data, &ctx.http, guild_id, |filters| {
+                    filters.pitch = pitch
+                })
+                .await?
+            }
+            "eq" => {
+                let (bass, mid, treble) = (number("bass"), number("mid"), number("treble"));
+                set(&ctx.data, &ctx.http, guild_id, |filters| {
+                    filters.bass = bass.unwrap_or(filters.bass);
+                    filters.mid = mid.unwrap_or(filters.mid);
+                    filters.treble = treble.unwrap_or(filters.treble);
+                })
+                .await?
+            }
+            "reset" => {
+                set(&ctx.data, &ctx.http, guild_id, |filters| {
+                    *filters = Filters::default()
+                })
+                .await?
+            }
+            _ => return Err(BeatError::NoValidCommand),
+        };
+
+        // Delete ephemeral response
+        command.delete_response(ctx).await?;
+    }
+
+    Ok(())
+}
+
+/// Changes the filters of a guild, which the playing track picks up right away unless it was
+/// loaded without any, and shows them in the queue message.
+pub async fn set(
+    data: &Arc<RwLock<TypeMap>>,
+    http: &Http,
+    guild_id: GuildId,
+    change: impl FnOnce(&mut Filters),
+) -> Result<Filters, BeatError> {
+    let settings = settings(data, guild_id).await;
+    let queue_lock = {
+        let guard = data.read().await;
+        guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
+    };
+
+    let maybe_queue = queue_lock.read().await;
+    let queue = maybe_queue.get(&guild_id).ok_or(BeatError::NoQueue)?;
+
+    let filters = {
+        let mut filters = queue
+            .filters
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        change(&mut filters);
+        *filters
+    };
+
+    update_message(http, queue, &settings).await?;
+    publish(data, guild_id, queue).await;
+
+    Ok(filters)
+}
@@ -0,0 +1,107 @@
+use crate::commands::play::play;
+use crate::errors::errors::BeatError;
+use crate::history::history::entries;
+use crate::messages::messages::to_history_page;
+use serde_json::json;
+use serenity::all::Interaction;
+use serenity::builder::CreateCommand;
+use serenity::client::Context;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PAGE_SIZE: usize = 10;
+/// Pages left untouched for longer than this stop responding to their navigation buttons.
+const PAGE_TIMEOUT_SECS: u64 = 300;
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("history").description("Shows the tracks played on this server")
+}
+
+pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    if let Interaction::Command(command) = interaction {
+        let guild_id = command.guild_id.ok_or(BeatError::NoGuild)?;
+
+        let mut history = entries(guild_id);
+        history.reverse();
+
+        let mut page = to_history_page(&history, 0, PAGE_SIZE, now);
+        page["flags"] = json!(64);
+
+        ctx.http
+            .create_interaction_response(
+                command.id,
+                &command.token,
+                &json!({"type": 4, "data": page}),
+                vec![],
+            )
+            .await?;
+    } else if let Interaction::Component(component) = interaction {
+        let guild_id = component.guild_id.ok_or(BeatError::NoGuild)?;
+
+        let mut history = entries(guild_id);
+        history.reverse();
+
+        // history:play:<started_at> or history:<action>:<page>:<issued>
+        let parts: Vec<&str> = component.data.custom_id.split(':').collect();
+        match parts.as_slice() {
+            ["history", "play", started_at] => {
+                let entry = history
+                    .iter()
+                    .find(|entry| entry.started_at.to_string() == *started_at)
+                    .ok_or(BeatError::NoSuchTrack)?;
+
+                component.defer_ephemeral(ctx).await?;
+                play(ctx, interaction, Some(&entry.url), None).await?;
+
+                let title = entry.title.as_deref().unwrap_or(&entry.url);
+                ctx.http
+                    .edit_original_interaction_response(
+                        &component.token,
+                        &json!({ "content": format!("Added **{}** to the queue.", title) }),
+                        vec![],
+                    )
+                    .await?;
+            }
+            ["history", action, page, issued] => {
+                let page = page.parse::<usize>().unwrap_or(0);
+                let issued = issued.parse::<u64>().unwrap_or(0);
+
+                let data = if now.saturating_sub(issued) > PAGE_TIMEOUT_SECS {
+                    json!({
+                        "content": "_This history view expired, use `/history` again._",
+                        "embeds": [],
+                        "components": []
+                    })
+                } else {
+                    let last = history.len().saturating_sub(1) / PAGE_SIZE;
+
+                    let target = match *action {
+                        "first" => 0,
+                        "prev" => page.saturating_sub(1),
+                        "next" => page + 1,
+                        "last" => last,
+                        _ => page,
+                    };
+
+                    to_history_page(&history, target, PAGE_SIZE, now)
+                };
+
+                ctx.http
+                    .create_interaction_response(
+                        component.id,
+                        &component.token,
+                        &json!({"type": 7, "data": data}),
+                        vec![],
+                    )
+                    .await?;
+            }
+            _ => return Err(BeatError::NoValidCommand),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use crate::commands::prev::jump_to;
+use crate::errors::errors::BeatError;
+use serenity::all::{ComponentInteractionDataKind, Interaction};
+use serenity::client::Context;
+
+/// Handles the jump select menu of the queue message.
+pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatError> {
+    if let Interaction::Component(component) = interaction {
+        component.defer_ephemeral(ctx).await?;
+        component.delete_response(ctx).await?;
+        let guild_id = component.guild_id.ok_or(BeatError::NoGuild)?;
+
+        let target = match &component.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => values
+                .first()
+                .and_then(|value| value.parse::<usize>().ok())
+                .ok_or(BeatError::NoValidCommand)?,
+            _ => return Err(BeatError::NoValidCommand),
+        };
+
+        jump_to(&ctx.data, guild_id, target).await?;
+    }
+
+    Ok(())
+}
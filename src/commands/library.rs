@@ -0,0 +1,164 @@
+use crate::commands::play::play;
+use crate::errors::errors::BeatError;
+use crate::library::library::{LibraryKey, Tag, root, scan};
+use crate::messages::messages::to_library_results;
+use serde_json::json;
+use serenity::all::{ComponentInteractionDataKind, Interaction};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::client::Context;
+use serenity::model::application::{CommandOptionType, ResolvedOption, ResolvedValue};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Pickers left unused for longer than this are closed.
+const PICK_TIMEOUT_SECS: u64 = 300;
+const EXPIRED: &str = "_This search expired, use `/library search` again._";
+
+pub fn register() -> CreateCommand {
+    let subcommand = |name: &str, description: &str| {
+        CreateCommandOption::new(CommandOptionType::SubCommand, name, description)
+    };
+
+    CreateCommand::new("library")
+        .description("Plays files of the local music library")
+        .add_option(
+            subcommand("search", "Searches the library by tag")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::String, "query", "Words to find")
+                        .required(true)
+                        .max_length(200),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::String, "tag", "Tag to search in")
+                        .add_string_choice("Title", "title")
+                        .add_string_choice("Artist", "artist")
+                        .add_string_choice("Album", "album"),
+                ),
+        )
+        .add_option(subcommand("scan", "Indexes the library again"))
+}
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &Interaction,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BeatError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    if root().is_none() {
+        return Err(BeatError::Other("No music library is configured"));
+    }
+    let library_lock = {
+        let guard = ctx.data.read().await;
+        guard
+            .get::<LibraryKey>()
+            .cloned()
+            .ok_or(BeatError::Other("No music library is configured"))?
+    };
+
+    if let Interaction::Command(command) = interaction {
+        command.defer_ephemeral(ctx).await?;
+
+        let Some(ResolvedOption {
+            name,
+            value: ResolvedValue::SubCommand(arguments),
+            ..
+        }) = options.first()
+        else {
+            return Err(BeatError::NoValidCommand);
+        };
+        let string = |name: &str| {
+            arguments.iter().find_map(|option| match option.value {
+                ResolvedValue::String(value) if option.name == name => Some(value),
+                _ => None,
+            })
+        };
+
+        let response = match *name {
+            "search" => {
+                let query = string("query").ok_or(BeatError::NoValidCommand)?;
+                let tag = Tag::from_name(string("tag").unwrap_or_default());
+
+                let library = library_lock.read().await;
+                to_library_results(query, &library.search(query, tag), library.scanned_at, now)
+            }
+            "scan" => {
+                let count = scan(&ctx.data).await.unwrap_or_default();
+                json!({ "content": format!("Indexed {} files.", count) })
+            }
+            _ => return Err(BeatError::NoValidCommand),
+        };
+
+        ctx.http
+            .edit_original_interaction_response(&command.token, &response, vec![])
+            .await?;
+    } else if let Interaction::Component(component) = interaction {
+        // library:<scanned_at>:<issued>
+        let (scanned_at, issued) = match component.data.custom_id.split(':').collect::<Vec<_>>()[..]
+        {
+            ["library", scanned_at, issued] => (
+                scanned_at.parse::<u64>().unwrap_or(0),
+                issued.parse::<u64>().unwrap_or(0),
+            ),
+            _ => return Err(BeatError::NoValidCommand),
+        };
+        let index = match &component.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => {
+                values.first().and_then(|value| value.parse::<usize>().ok())
+            }
+            _ => None,
+        }
+        .ok_or(BeatError::NoValidCommand)?;
+
+        // Indices only hold for the scan the picker was built from
+        let url = {
+            let library = library_lock.read().await;
+            library
+                .tracks
+                .get(index)
+                .filter(|_| library.scanned_at == scanned_at)
+                .and_then(|track| track.source_url.clone())
+        };
+
+        let Some(url) = url.filter(|_| now.saturating_sub(issued) <= PICK_TIMEOUT_SECS) else {
+            ctx.http
+                .create_interaction_response(
+                    component.id,
+                    &component.token,
+                    &json!({"type": 7, "data": {"content": EXPIRED, "components": []}}),
+                    vec![],
+                )
+                .await?;
+            return Ok(());
+        };
+
+        // Drop the menu right away so the file can't be picked twice
+        ctx.http
+            .create_interaction_response(
+                component.id,
+                &component.token,
+                &json!({"type": 7, "data": {"content": "_Adding the track..._", "components": []}}),
+                vec![],
+            )
+            .await?;
+
+        let result = play(ctx, interaction, Some(&url), None).await;
+        let content = match &result {
+            Ok(()) => String::from("Added to the queue."),
+            Err(error) => format!("_{}_", error),
+        };
+        ctx.http
+            .edit_original_interaction_response(
+                &component.token,
+                &json!({ "content": content }),
+                vec![],
+            )
+            .await?;
+
+        result?;
+    }
+
+    Ok(())
+}
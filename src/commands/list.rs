@@ -26,7 +26,12 @@ pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatErr
         for entry in fs::read_dir(dir_name)? {
             let entry = entry?;
             let path = entry.path();
-            if path.is_file() {
+            // Only playlists, the directory also holds the guild settings
+            if path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|extension| extension == "playlist")
+            {
 
                 let track_count = fs::read(path.clone())?.lines().count();
 
@@ -38,7 +43,7 @@ pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatErr
                         .to_str()
                         .unwrap()
                         .split(".")
-                        .nth(0)
+                        .next()
                         .unwrap(),
                     track_count
                 );
@@ -48,7 +53,7 @@ pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatErr
         let body = format!(
             "{}{}",
             collected,
-            if collected.len() == 0 { "_None_" } else { "" }
+            if collected.is_empty() { "_None_" } else { "" }
         );
 
         let json = json!({"embeds": [
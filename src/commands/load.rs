@@ -1,6 +1,6 @@
-use crate::commands::play::{connect_and_handle, insert_track};
+use crate::commands::play::{TrackRequest, connect_and_handle, insert_track};
 use crate::errors::errors::BeatError;
-use crate::{HttpKey, QueueKey};
+use crate::{HttpKey, QueueKey, TrackSource};
 use serenity::all::{
     CommandOptionType, Context, CreateCommand, CreateCommandOption, Interaction, ResolvedOption,
     ResolvedValue,
@@ -8,6 +8,7 @@ use serenity::all::{
 use songbird::SongbirdKey;
 use std::fs;
 use std::fs::create_dir_all;
+use tracing::debug;
 
 pub fn register() -> CreateCommand {
     CreateCommand::new("load")
@@ -35,8 +36,7 @@ pub async fn run(
         value: ResolvedValue::String(name),
         ..
     }) = options.first()
-    {
-        if let Some((guild_id, channel_id, user_id)) =
+        && let Some((guild_id, channel_id, user_id)) =
             if let Interaction::Command(command) = interaction {
                 command.defer_ephemeral(ctx).await?;
                 Some((
@@ -47,105 +47,110 @@ pub async fn run(
             } else {
                 None
             }
-        {
-            let to_connect = ctx
-                .cache
-                .guild(guild_id)
-                .ok_or(BeatError::Other("Beat has no information about that guild"))?
-                .voice_states
-                .get(&user_id)
-                .and_then(|voice_state| voice_state.channel_id)
-                .ok_or(BeatError::Other("User connected to a channel"))?;
-
-            let manager = songbird::get(ctx).await.ok_or(BeatError::NoSongbird)?;
-
-            if let None = manager.get(guild_id) {
-                connect_and_handle(ctx, guild_id, channel_id, to_connect, &manager).await?;
-
-                let queue_lock = {
-                    let guard = ctx.data.read().await;
-                    guard.get::<QueueKey>().unwrap().clone()
-                };
+    {
+        let to_connect = ctx
+            .cache
+            .guild(guild_id)
+            .ok_or(BeatError::Other("Beat has no information about that guild"))?
+            .voice_states
+            .get(&user_id)
+            .and_then(|voice_state| voice_state.channel_id)
+            .ok_or(BeatError::Other("User connected to a channel"))?;
+
+        let manager = songbird::get(ctx).await.ok_or(BeatError::NoSongbird)?;
+
+        if manager.get(guild_id).is_none() {
+            connect_and_handle(ctx, guild_id, channel_id, to_connect, &manager).await?;
+
+            let queue_lock = {
+                let guard = ctx.data.read().await;
+                guard.get::<QueueKey>().unwrap().clone()
+            };
 
-                let mut maybe_queue = queue_lock.write().await;
-
-                if let Some(existing_queue) = maybe_queue.get_mut(&guild_id) {
-                    println!(
-                        "Queue already exists while joining channel: {:?}, clearing previous state",
-                        existing_queue
-                    );
-
-                    if let Some(message_id) = existing_queue.message_id {
-                        println!(
-                            "Deleting message at channel {} with ID {}",
-                            channel_id, message_id
-                        );
-
-                        ctx.http
-                            .delete_message(channel_id, message_id, Some("Dangling message"))
-                            .await
-                            .unwrap_or_default();
-                    }
-
-                    println!("Removing tracklist for guild {:?}", guild_id);
-
-                    // Disconnect and clear Songbird for the guild
-                    let manager_lock = {
-                        let guard = ctx.data.read().await;
-                        guard.get::<SongbirdKey>().unwrap().clone()
-                    };
-
-                    // Disconnect and clear Songbird for the guild
-                    manager_lock
-                        .get(guild_id)
-                        .unwrap()
-                        .lock()
-                        .await
-                        .queue()
-                        .stop();
+            let mut maybe_queue = queue_lock.write().await;
 
-                    println!("Tracklist removed for guild {:?}", guild_id);
+            if let Some(existing_queue) = maybe_queue.get_mut(&guild_id) {
+                debug!(
+                    playing_index = existing_queue.playing_index,
+                    tracks = existing_queue.queue.len(),
+                    "Queue already exists while joining channel, clearing previous state"
+                );
 
-                    // Remove local data
-                    maybe_queue
-                        .get_mut(&guild_id)
-                        .ok_or(BeatError::NoQueue)?
-                        .reset();
+                if let Some(message_id) = existing_queue.message_id {
+                    debug!(%message_id, "Deleting dangling queue message");
 
-                    maybe_queue
-                        .get_mut(&guild_id)
-                        .ok_or(BeatError::NoQueue)?
-                        .stopping = false
+                    ctx.http
+                        .delete_message(
+                            existing_queue.channel_id.unwrap_or(channel_id),
+                            message_id,
+                            Some("Dangling message"),
+                        )
+                        .await
+                        .unwrap_or_default();
                 }
-            };
 
-            let http_client = {
-                let data = ctx.data.read().await;
-                data.get::<HttpKey>().cloned().ok_or(BeatError::NoHttp)?
-            };
+                // Disconnect and clear Songbird for the guild
+                let manager_lock = {
+                    let guard = ctx.data.read().await;
+                    guard.get::<SongbirdKey>().unwrap().clone()
+                };
 
-            let dir_name = format!("./{}", guild_id);
-            create_dir_all(dir_name.clone())?;
-            let file_name = format!("{}/{}.playlist", dir_name, name);
-            let content = fs::read_to_string(file_name)?;
-            let urls = content.split("\n").collect::<Vec<&str>>();
-
-            for i in 0..urls.len() {
-                should_delete = insert_track(
-                    ctx,
-                    interaction,
-                    guild_id,
-                    channel_id,
-                    String::from(urls[i]),
-                    manager.get(guild_id).ok_or(BeatError::NoManager)?,
-                    false,
-                    i == 0,
-                    http_client.clone(),
-                )
-                .await
-                // Ignore error in a playlist, keep loading next ones
-                .unwrap_or(false);
+                // Disconnect and clear Songbird for the guild
+                manager_lock
+                    .get(guild_id)
+                    .unwrap()
+                    .lock()
+                    .await
+                    .queue()
+                    .stop();
+
+                debug!("Tracklist removed");
+
+                // Remove local data
+                maybe_queue
+                    .get_mut(&guild_id)
+                    .ok_or(BeatError::NoQueue)?
+                    .reset();
+
+                maybe_queue
+                    .get_mut(&guild_id)
+                    .ok_or(BeatError::NoQueue)?
+                    .stopping = false
             }
+        };
+
+        let http_client = {
+            let data = ctx.data.read().await;
+            data.get::<HttpKey>().cloned().ok_or(BeatError::NoHttp)?
+        };
+
+        let dir_name = format!("./{}", guild_id);
+        create_dir_all(dir_name.clone())?;
+        let file_name = format!("{}/{}.playlist", dir_name, name);
+        let content = fs::read_to_string(file_name)?;
+        let urls = content.split("\n").collect::<Vec<&str>>();
+
+        for (i, url) in urls.into_iter().enumerate() {
+            let request = TrackRequest {
+                guild_id,
+                channel_id,
+                url: String::from(url),
+                requester: Some(user_id),
+                source: TrackSource::Playlist(String::from(*name)),
+                position: None,
+            };
+
+            should_delete = insert_track(
+                ctx,
+                interaction,
+                manager.get(guild_id).ok_or(BeatError::NoManager)?,
+                request,
+                i == 0,
+                http_client.clone(),
+            )
+            .await
+            // Ignore error in a playlist, keep loading next ones
+            .unwrap_or(false);
         }
     }
 
@@ -1,10 +1,24 @@
 pub(crate) mod clean;
+pub(crate) mod jump;
 pub(crate) mod next;
 pub(crate) mod pause;
 pub(crate) mod play;
 pub(crate) mod prev;
+pub(crate) mod queue;
 pub(crate) mod repeat;
+pub(crate) mod settings;
 pub(crate) mod stop;
+pub(crate) mod volume;
 pub(crate) mod save;
 pub(crate) mod load;
+pub(crate) mod delete;
 pub(crate) mod list;
+pub(crate) mod seek;
+pub(crate) mod filter;
+pub(crate) mod playnext;
+pub(crate) mod search;
+pub(crate) mod autocomplete;
+pub(crate) mod history;
+pub(crate) mod autoplay;
+pub(crate) mod library;
+pub(crate) mod podcast;
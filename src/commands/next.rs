@@ -1,35 +1,59 @@
+use crate::QueueKey;
+use crate::commands::play::songbird_manager;
 use crate::errors::errors::BeatError;
-use serenity::all::Interaction;
+use crate::http::api::publish;
+use crate::messages::messages::update_message;
+use crate::permissions::permissions::may_skip;
+use crate::settings::settings::settings;
+use serde_json::json;
+use serenity::all::{GuildId, Interaction, UserId};
 use serenity::builder::CreateCommand;
 use serenity::client::Context;
+use serenity::http::Http;
+use serenity::prelude::TypeMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::debug;
 
 pub fn register() -> CreateCommand {
-    CreateCommand::new("next").description("Jumps to the next song")
+    CreateCommand::new("next").description("Jumps to the next song, or votes to skip it")
 }
 
 pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatError> {
-    if let Some(guild_id) = if let Interaction::Command(command) = interaction {
+    if let Some((guild_id, member)) = if let Interaction::Command(command) = interaction {
         command.defer_ephemeral(ctx).await?;
-        Some(command.guild_id.ok_or(BeatError::NoGuild)?)
+        Some((
+            command.guild_id.ok_or(BeatError::NoGuild)?,
+            command.member.as_deref().ok_or(BeatError::NoGuild)?,
+        ))
     } else if let Interaction::Component(component) = interaction {
         component.defer_ephemeral(ctx).await?;
         component.delete_response(ctx).await?;
-        Some(component.guild_id.ok_or(BeatError::NoGuild)?)
+        Some((
+            component.guild_id.ok_or(BeatError::NoGuild)?,
+            component.member.as_ref().ok_or(BeatError::NoGuild)?,
+        ))
     } else {
         None
     } {
-        // Get Songbird
-        let manager = songbird::get(ctx)
-            .await
-            .ok_or(BeatError::NoSongbird)?
-            .clone();
-        let handler_lock = manager.get(guild_id).ok_or(BeatError::NoManager)?;
-
-        // Skips the track
-        {
-            let guard = handler_lock.lock().await;
-            let x = guard.queue();
-            x.skip()?;
+        if may_skip(ctx, guild_id, member).await? {
+            skip(&ctx.data, guild_id).await?;
+        } else {
+            let listeners = listeners(ctx, guild_id)?;
+            let tally = vote(&ctx.data, &ctx.http, guild_id, member.user.id, listeners).await?;
+
+            if let (Some((votes, needed)), Interaction::Command(command)) = (tally, interaction) {
+                // Tell the voter where the vote stands instead of deleting the response
+                let content = format!("Vote registered, {}/{} to skip", votes, needed);
+                ctx.http
+                    .edit_original_interaction_response(
+                        &command.token,
+                        &json!({ "content": content }),
+                        vec![],
+                    )
+                    .await?;
+                return Ok(());
+            }
         }
     }
 
@@ -40,3 +64,107 @@ pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatErr
 
     Ok(())
 }
+
+pub async fn skip(data: &Arc<RwLock<TypeMap>>, guild_id: GuildId) -> Result<(), BeatError> {
+    // Get Songbird
+    let manager = songbird_manager(data).await?;
+    let handler_lock = manager.get(guild_id).ok_or(BeatError::NoManager)?;
+
+    // Skips the track
+    {
+        let guard = handler_lock.lock().await;
+        let x = guard.queue();
+        x.skip()?;
+    }
+
+    Ok(())
+}
+
+/// Votes it takes to skip with `listeners` people in the channel, at least one.
+fn votes_needed(listeners: usize, ratio: f64) -> usize {
+    ((listeners as f64 * ratio).ceil() as usize).max(1)
+}
+
+/// Non-bot members in Beat's voice channel.
+fn listeners(ctx: &Context, guild_id: GuildId) -> Result<usize, BeatError> {
+    let bot_id = ctx.cache.current_user().id;
+    let guild = ctx
+        .cache
+        .guild(guild_id)
+        .ok_or(BeatError::Other("Beat has no information about that guild"))?;
+
+    let Some(channel_id) = guild
+        .voice_states
+        .get(&bot_id)
+        .and_then(|voice_state| voice_state.channel_id)
+    else {
+        return Ok(0);
+    };
+
+    Ok(guild
+        .voice_states
+        .values()
+        .filter(|voice_state| voice_state.channel_id == Some(channel_id))
+        .filter(|voice_state| {
+            voice_state
+                .member
+                .as_ref()
+                .map(|member| !member.user.bot)
+                .unwrap_or(voice_state.user_id != bot_id)
+        })
+        .count())
+}
+
+/// Registers a vote to skip the current track, skipping it once enough listeners agree.
+/// Returns the votes and the votes needed while the vote is still open.
+pub async fn vote(
+    data: &Arc<RwLock<TypeMap>>,
+    http: &Http,
+    guild_id: GuildId,
+    user_id: UserId,
+    listeners: usize,
+) -> Result<Option<(usize, usize)>, BeatError> {
+    let settings = settings(data, guild_id).await;
+    let queue_lock = {
+        let guard = data.read().await;
+        guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
+    };
+
+    {
+        let mut maybe_queue = queue_lock.write().await;
+        let queue = maybe_queue.get_mut(&guild_id).ok_or(BeatError::NoQueue)?;
+
+        queue.skip_votes.insert(user_id);
+        // Listeners may have left or joined since the last vote
+        queue.votes_needed = votes_needed(listeners, settings.vote_skip_ratio);
+
+        let votes = queue.skip_votes.len();
+        debug!(votes, needed = queue.votes_needed, "Skip vote registered");
+
+        if votes < queue.votes_needed {
+            update_message(http, queue, &settings).await?;
+            publish(data, guild_id, queue).await;
+
+            return Ok(Some((votes, queue.votes_needed)));
+        }
+    }
+
+    // Votes are cleared once the next track starts
+    skip(data, guild_id).await?;
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn votes_needed_rounds_up() {
+        assert_eq!(votes_needed(0, 0.5), 1);
+        assert_eq!(votes_needed(1, 0.5), 1);
+        assert_eq!(votes_needed(3, 0.5), 2);
+        assert_eq!(votes_needed(4, 0.5), 2);
+        assert_eq!(votes_needed(5, 1.0), 5);
+    }
+}
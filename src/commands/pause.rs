@@ -1,67 +1,84 @@
 use crate::QueueKey;
+use crate::commands::play::songbird_manager;
 use crate::errors::errors::BeatError;
-use crate::messages::messages::to_embed;
-use serenity::all::Interaction;
+use crate::http::api::publish;
+use crate::messages::messages::update_message;
+use crate::settings::settings::settings;
+use serenity::all::{GuildId, Interaction};
 use serenity::builder::CreateCommand;
 use serenity::client::Context;
+use serenity::http::Http;
+use serenity::prelude::TypeMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
 
 pub fn register() -> CreateCommand {
     CreateCommand::new("pause").description("Toggle pause")
 }
 
 pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatError> {
-    if let Some((guild_id, channel_id)) = if let Interaction::Command(command) = interaction {
+    if let Some(guild_id) = if let Interaction::Command(command) = interaction {
         command.defer_ephemeral(ctx).await?;
-        Some((
-            command.guild_id.ok_or(BeatError::NoGuild)?,
-            command.channel_id,
-        ))
+        Some(command.guild_id.ok_or(BeatError::NoGuild)?)
     } else if let Interaction::Component(component) = interaction {
         component.defer_ephemeral(ctx).await?;
         component.delete_response(ctx).await?;
-        Some((
-            component.guild_id.ok_or(BeatError::NoGuild)?,
-            component.channel_id,
-        ))
+        Some(component.guild_id.ok_or(BeatError::NoGuild)?)
     } else {
         None
     } {
-        let queue_lock = {
-            let guard = ctx.data.write().await;
-            guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
-        };
+        toggle(&ctx.data, &ctx.http, guild_id).await?;
+    }
 
-        let mut maybe_queue = queue_lock.write().await;
+    if let Interaction::Command(command) = interaction {
+        // Delete ephemeral response
+        command.delete_response(ctx).await?;
+    }
 
-        // Delete the queue message
-        if let Some(queue) = maybe_queue.get_mut(&guild_id) {
-            queue.pause = !queue.pause;
+    Ok(())
+}
 
-            // Get Songbird
-            let manager = songbird::get(ctx)
-                .await
-                .ok_or(BeatError::NoSongbird)?
-                .clone();
-            let handler_lock = manager.get(guild_id).ok_or(BeatError::NoManager)?;
+pub async fn toggle(
+    data: &Arc<RwLock<TypeMap>>,
+    http: &Http,
+    guild_id: GuildId,
+) -> Result<(), BeatError> {
+    let settings = settings(data, guild_id).await;
+    let queue_lock = {
+        let guard = data.read().await;
+        guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
+    };
 
-            // Enable loop
-            if queue.pause {
-                handler_lock.lock().await.queue().pause()?;
-            } else {
-                handler_lock.lock().await.queue().resume()?;
-            }
+    let mut maybe_queue = queue_lock.write().await;
 
-            if let Some(message_id) = queue.message_id {
-                ctx.http
-                    .edit_message(channel_id, message_id, &to_embed(queue), vec![])
-                    .await?;
+    if let Some(queue) = maybe_queue.get_mut(&guild_id) {
+        queue.pause = !queue.pause;
+
+        // Get Songbird
+        let manager = songbird_manager(data).await?;
+        let handler_lock = manager.get(guild_id).ok_or(BeatError::NoManager)?;
+
+        // Enable loop
+        if queue.pause {
+            handler_lock.lock().await.queue().pause()?;
+            // The end of a crossfade is no longer in Songbird's queue, cut it short
+            if let Some(outgoing) = &queue.crossfading {
+                outgoing.stop().unwrap_or_default();
             }
+        } else {
+            handler_lock.lock().await.queue().resume()?;
         }
-    }
 
-    if let Interaction::Command(command) = interaction {
-        // Delete ephemeral response
-        command.delete_response(ctx).await?;
+        // Freeze the progress bar where playback stopped
+        let current = handler_lock.lock().await.queue().current();
+        if let Some(current) = current
+            && let Ok(info) = current.get_info().await
+        {
+            queue.position = info.position;
+        }
+
+        update_message(http, queue, &settings).await?;
+        publish(data, guild_id, queue).await;
     }
 
     Ok(())
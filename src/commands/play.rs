@@ -1,8 +1,20 @@
+use crate::audio::direct::is_youtube;
+use crate::audio::source::Source;
+use crate::audio::{crossfade, direct, filters, icy, loudness};
+use crate::commands::autoplay;
+use crate::commands::prev::move_last;
+use crate::commands::seek::start_position;
 use crate::errors::errors::BeatError;
-use crate::messages::messages::to_embed;
-use crate::{HttpKey, Queue, QueueKey};
+use crate::history::history;
+use crate::http::api::publish;
+use crate::library::library;
+use crate::messages::messages::{to_embed, update_message};
+use crate::podcast::podcast;
+use crate::settings::settings::{Settings, settings};
+use crate::telemetry::{logging, metrics};
+use crate::{HttpKey, Queue, QueueKey, QueuedTrack, TrackSource};
 use reqwest::Client;
-use serenity::all::{ChannelId, GuildId, Interaction};
+use serenity::all::{ChannelId, GuildId, Interaction, UserId};
 use serenity::async_trait;
 use serenity::builder::{CreateCommand, CreateCommandOption};
 use serenity::client::Context;
@@ -10,13 +22,15 @@ use serenity::http::Http;
 use serenity::json::Value;
 use serenity::model::application::{CommandOptionType, ResolvedOption, ResolvedValue};
 use serenity::prelude::TypeMap;
-use songbird::input::{Compose, YoutubeDl};
+use songbird::input::{Compose, File, HttpRequest, YoutubeDl};
 use songbird::{Call, Event, EventContext, EventHandler, Songbird, SongbirdKey, TrackEvent};
-use std::io::{BufRead, BufReader};
-use std::process::{Command, Stdio};
+use std::process::Stdio;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, info, info_span, warn};
+use tracing_futures::Instrument;
 use url::Url;
 
 struct TrackErrorNotifier;
@@ -44,7 +58,20 @@ pub fn register() -> CreateCommand {
                 "track",
                 "The track to play or add to the queue",
             )
-            .required(true),
+            .set_autocomplete(true),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Attachment,
+            "file",
+            "An audio file to play instead",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "position",
+                "Position among the upcoming tracks, 1 to play it next",
+            )
+            .min_int_value(1),
         )
 }
 
@@ -52,17 +79,70 @@ pub async fn run(
     ctx: &Context,
     interaction: &Interaction,
     options: &[ResolvedOption<'_>],
+) -> Result<(), BeatError> {
+    let url = options.iter().find_map(|option| match option.value {
+        ResolvedValue::String(url) if option.name == "track" => Some(url),
+        ResolvedValue::Attachment(attachment) if option.name == "file" => {
+            Some(attachment.url.as_str())
+        }
+        _ => None,
+    });
+    // Discord tells the type of uploads, anything but audio or video is not worth fetching
+    let is_media = options.iter().all(|option| match option.value {
+        ResolvedValue::Attachment(attachment) => {
+            attachment
+                .content_type
+                .as_deref()
+                .is_none_or(|content_type| {
+                    content_type.starts_with("audio/") || content_type.starts_with("video/")
+                })
+        }
+        _ => true,
+    });
+    if url.is_none() {
+        return Err(BeatError::Other("Give a track or a file to play"));
+    }
+    if !is_media {
+        return Err(BeatError::Other("The file is not audio"));
+    }
+    let position = options.iter().find_map(|option| match option.value {
+        ResolvedValue::Integer(position) if option.name == "position" => {
+            Some(position.max(1) as usize)
+        }
+        _ => None,
+    });
+
+    play(ctx, interaction, url, position).await
+}
+
+/// Plays `url`, appended to the queue or inserted at `position` among the upcoming tracks.
+pub async fn play(
+    ctx: &Context,
+    interaction: &Interaction,
+    url: Option<&str>,
+    position: Option<usize>,
+) -> Result<(), BeatError> {
+    if let Interaction::Command(command) = interaction {
+        command.defer_ephemeral(ctx).await?;
+    }
+
+    play_from(ctx, interaction, url, None, position).await
+}
+
+/// Same as [`play`], for a single track whose `source` is known already rather than guessed from
+/// the link. Commands must be deferred already, as finding the track may take a while.
+pub async fn play_from(
+    ctx: &Context,
+    interaction: &Interaction,
+    url: Option<&str>,
+    source: Option<TrackSource>,
+    position: Option<usize>,
 ) -> Result<(), BeatError> {
     let mut should_delete = true;
 
-    if let Some(ResolvedOption {
-        value: ResolvedValue::String(url),
-        ..
-    }) = options.first()
-    {
-        if let Some((guild_id, channel_id, user_id)) =
+    if let Some(url) = url
+        && let Some((guild_id, channel_id, user_id)) =
             if let Interaction::Command(command) = interaction {
-                command.defer_ephemeral(ctx).await?;
                 Some((
                     command.guild_id.ok_or(BeatError::NoGuild)?,
                     command.channel_id,
@@ -77,128 +157,107 @@ pub async fn run(
             } else {
                 None
             }
-        {
-            let url = String::from(*url);
-
-            let to_connect = ctx
-                .cache
-                .guild(guild_id)
-                .ok_or(BeatError::Other("Beat has no information about that guild"))?
-                .voice_states
-                .get(&user_id)
-                .and_then(|voice_state| voice_state.channel_id)
-                .ok_or(BeatError::Other("User connected to a channel"))?;
+    {
+        let url = String::from(url);
 
-            let manager = songbird::get(ctx).await.ok_or(BeatError::NoSongbird)?;
+        let to_connect = ctx
+            .cache
+            .guild(guild_id)
+            .ok_or(BeatError::Other("Beat has no information about that guild"))?
+            .voice_states
+            .get(&user_id)
+            .and_then(|voice_state| voice_state.channel_id)
+            .ok_or(BeatError::Other("User connected to a channel"))?;
 
-            if let None = manager.get(guild_id) {
-                connect_and_handle(ctx, guild_id, channel_id, to_connect, &manager).await?;
+        let manager = songbird::get(ctx).await.ok_or(BeatError::NoSongbird)?;
 
-                let queue_lock = {
-                    let guard = ctx.data.read().await;
-                    guard.get::<QueueKey>().unwrap().clone()
-                };
-
-                let mut maybe_queue = queue_lock.write().await;
-
-                if let Some(existing_queue) = maybe_queue.get_mut(&guild_id) {
-                    println!(
-                        "Queue already exists while joining channel: {:?}, clearing previous state",
-                        existing_queue
-                    );
+        if manager.get(guild_id).is_none() {
+            connect_and_handle(ctx, guild_id, channel_id, to_connect, &manager).await?;
 
-                    if let Some(message_id) = existing_queue.message_id {
-                        println!(
-                            "Deleting message at channel {} with ID {}",
-                            channel_id, message_id
-                        );
+            let queue_lock = {
+                let guard = ctx.data.read().await;
+                guard.get::<QueueKey>().unwrap().clone()
+            };
 
-                        ctx.http
-                            .delete_message(channel_id, message_id, Some("Dangling message"))
-                            .await
-                            .unwrap_or_default();
-                    }
+            let mut maybe_queue = queue_lock.write().await;
 
-                    println!("Removing tracklist for guild {:?}", guild_id);
+            if let Some(existing_queue) = maybe_queue.get_mut(&guild_id) {
+                debug!(
+                    playing_index = existing_queue.playing_index,
+                    tracks = existing_queue.queue.len(),
+                    "Queue already exists while joining channel, clearing previous state"
+                );
 
-                    // Disconnect and clear Songbird for the guild
-                    let manager_lock = {
-                        let guard = ctx.data.read().await;
-                        guard.get::<SongbirdKey>().unwrap().clone()
-                    };
+                if let Some(message_id) = existing_queue.message_id {
+                    debug!(%message_id, "Deleting dangling queue message");
 
-                    // Disconnect and clear Songbird for the guild
-                    manager_lock
-                        .get(guild_id)
-                        .unwrap()
-                        .lock()
+                    ctx.http
+                        .delete_message(
+                            existing_queue.channel_id.unwrap_or(channel_id),
+                            message_id,
+                            Some("Dangling message"),
+                        )
                         .await
-                        .queue()
-                        .stop();
-
-                    println!("Tracklist removed for guild {:?}", guild_id);
-
-                    // Remove local data
-                    maybe_queue
-                        .get_mut(&guild_id)
-                        .ok_or(BeatError::NoQueue)?
-                        .reset_for_play();
+                        .unwrap_or_default();
                 }
-            };
 
-            let do_search = !url.starts_with("http");
-            let playlist = url.contains("list=");
+                // Disconnect and clear Songbird for the guild
+                let manager_lock = {
+                    let guard = ctx.data.read().await;
+                    guard.get::<SongbirdKey>().unwrap().clone()
+                };
 
-            let http_client = {
-                let data = ctx.data.read().await;
-                data.get::<HttpKey>().cloned().ok_or(BeatError::NoHttp)?
-            };
+                // Disconnect and clear Songbird for the guild
+                manager_lock
+                    .get(guild_id)
+                    .unwrap()
+                    .lock()
+                    .await
+                    .queue()
+                    .stop();
 
-            if playlist {
-                let parsed = Url::parse(url.as_str())?;
-                let index = parsed
-                    .query_pairs()
-                    .filter(|(key, _)| key == "index")
-                    .last()
-                    .map(|(_, value)| value.parse::<usize>().unwrap_or(1))
-                    .unwrap_or(1);
+                debug!("Tracklist removed");
 
-                let playlist = ytdl_playlist(url.clone())
-                    .await
-                    .ok_or(BeatError::Other("Empty playlist"))?
-                    .split_off(index - 1);
-
-                for i in 0..playlist.len() {
-                    should_delete = insert_track(
-                        ctx,
-                        interaction,
-                        guild_id,
-                        channel_id,
-                        playlist[i].clone(),
-                        manager.get(guild_id).ok_or(BeatError::NoManager)?,
-                        false,
-                        i == 0,
-                        http_client.clone(),
-                    )
-                    .await
-                    // Ignore error in a playlist, keep loading next ones
-                    .unwrap_or(true);
-                }
-            } else {
-                should_delete = insert_track(
-                    ctx,
-                    interaction,
-                    guild_id,
-                    channel_id,
-                    url,
-                    manager.get(guild_id).ok_or(BeatError::NoManager)?,
-                    do_search,
-                    true,
-                    http_client.clone(),
-                )
-                .await
-                .unwrap_or(true);
+                // Remove local data
+                maybe_queue
+                    .get_mut(&guild_id)
+                    .ok_or(BeatError::NoQueue)?
+                    .reset_for_play();
             }
+        };
+
+        let http_client = {
+            let data = ctx.data.read().await;
+            data.get::<HttpKey>().cloned().ok_or(BeatError::NoHttp)?
+        };
+
+        let (tracks, source) = match source {
+            Some(source) => (vec![url], source),
+            None => resolve_tracks(url, &http_client).await?,
+        };
+
+        for (i, track) in tracks.into_iter().enumerate() {
+            let request = TrackRequest {
+                guild_id,
+                channel_id,
+                url: track,
+                requester: Some(user_id),
+                source: source.clone(),
+                // Keep the order of a playlist inserted in the middle
+                position: position.map(|position| position + i),
+            };
+
+            should_delete = insert_track(
+                ctx,
+                interaction,
+                manager.get(guild_id).ok_or(BeatError::NoManager)?,
+                request,
+                i == 0,
+                http_client.clone(),
+            )
+            .await
+            // Ignore error in a playlist, keep loading next ones
+            .unwrap_or(true);
         }
     }
 
@@ -220,6 +279,7 @@ pub async fn connect_and_handle(
     manager: &Arc<Songbird>,
 ) -> Result<(), BeatError> {
     let lock = manager.join(guild_id, to_connect).await?;
+    metrics::voice_connected();
     let copy = lock.clone();
     let mut handler = copy.lock().await;
     handler.remove_all_global_events();
@@ -228,7 +288,7 @@ pub async fn connect_and_handle(
         TrackEvent::End.into(),
         OnTrackEnd {
             guild_id,
-            channel_id: channel_id,
+            channel_id,
             data: ctx.clone().data,
             http: ctx.clone().http,
         },
@@ -237,7 +297,7 @@ pub async fn connect_and_handle(
         TrackEvent::Play.into(),
         OnTrackStart {
             guild_id,
-            channel_id: channel_id,
+            channel_id,
             data: ctx.clone().data,
             http: ctx.clone().http,
         },
@@ -245,96 +305,369 @@ pub async fn connect_and_handle(
     Ok(())
 }
 
+/// Expands a `/play` argument into the tracks to enqueue, and where they come from.
+pub async fn resolve_tracks(
+    url: String,
+    http_client: &Client,
+) -> Result<(Vec<String>, TrackSource), BeatError> {
+    if direct::is_playlist(&url) {
+        let (name, entries) = direct::playlist(http_client, &url)
+            .await
+            .ok_or(BeatError::Other("Empty playlist"))?;
+
+        Ok((entries, TrackSource::Playlist(name)))
+    } else if url.starts_with("file:") {
+        library::local_path(&url).ok_or(BeatError::Other("Not a file of the library"))?;
+
+        Ok((vec![url], TrackSource::Library))
+    } else if url.contains("list=") {
+        let parsed = Url::parse(url.as_str())?;
+        let index = parsed
+            .query_pairs()
+            .filter(|(key, _)| key == "index")
+            .last()
+            .map(|(_, value)| value.parse::<usize>().unwrap_or(1))
+            .unwrap_or(1);
+
+        let (title, mut playlist) = ytdl_playlist(url.clone())
+            .await
+            .ok_or(BeatError::Other("Empty playlist"))?;
+        let playlist = playlist.split_off(index - 1);
+
+        Ok((playlist, TrackSource::Playlist(title.unwrap_or(url))))
+    } else if url.starts_with("http") {
+        Ok((vec![url], TrackSource::Url))
+    } else {
+        Ok((vec![url.clone()], TrackSource::Search(url)))
+    }
+}
+
+/// A track to add to a guild queue, see [`enqueue`].
+pub struct TrackRequest {
+    pub guild_id: GuildId,
+    /// Where the queue message is sent if the guild has none yet.
+    pub channel_id: ChannelId,
+    pub url: String,
+    pub requester: Option<UserId>,
+    pub source: TrackSource,
+    /// Among the upcoming tracks, 1 being right after the current one. Appended if `None`.
+    pub position: Option<usize>,
+}
+
 pub async fn insert_track(
     ctx: &Context,
     interaction: &Interaction,
-    guild_id: GuildId,
-    channel_id: ChannelId,
-    url: String,
     handler_lock: Arc<Mutex<Call>>,
-    do_search: bool,
+    request: TrackRequest,
     should_delete: bool,
     http_client: Client,
 ) -> Result<bool, BeatError> {
+    enqueue(&ctx.data, &ctx.http, handler_lock, request, http_client).await?;
+
+    if should_delete && let Interaction::Command(command) = interaction {
+        // Delete ephemeral response
+        command.delete_response(ctx).await?;
+        return Ok(false);
+    }
+
+    Ok(should_delete)
+}
+
+/// Resolves a track, adds it to the guild queue and to Songbird, and refreshes the queue
+/// message, sending it to the request's channel first if needed.
+pub async fn enqueue(
+    data: &Arc<RwLock<TypeMap>>,
+    http: &Arc<Http>,
+    handler_lock: Arc<Mutex<Call>>,
+    request: TrackRequest,
+    http_client: Client,
+) -> Result<(), BeatError> {
+    let TrackRequest {
+        guild_id,
+        channel_id,
+        url,
+        requester,
+        source,
+        position,
+    } = request;
+
     // let yt_dlp_args = env::var("YT_DLP_ARGS")
     //     .unwrap()
     //     .split(" ")
     //     .map(|str| String::from(str))
     //     .collect::<Vec<String>>();
 
+    let settings = settings(data, guild_id).await;
+
     let queue_lock = {
-        let guard = ctx.data.read().await;
+        let guard = data.read().await;
         guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
     };
 
-    let mut maybe_queue = queue_lock.write().await;
+    // Fail early rather than resolve a track that cannot be queued
+    admit(
+        queue_lock
+            .read()
+            .await
+            .get(&guild_id)
+            .ok_or(BeatError::NoQueue)?,
+        &settings,
+    )?;
+
+    // Resolving may take a while, the queue stays unlocked meanwhile
+    let do_search = source.is_search();
+    let local = library::local_path(&url);
+    // Plain audio links and radios skip yt-dlp
+    let direct = if do_search || local.is_some() || is_youtube(&url) {
+        None
+    } else {
+        direct::probe(&http_client, &url).await
+    };
 
-    if let Some(existing_queue) = maybe_queue.get_mut(&guild_id) {
-        if !existing_queue.stopping {
-            let src = if do_search {
-                YoutubeDl::new_search(http_client, url.clone()).user_args(vec![
-                    "-4".into(),
-                    "-f".into(),
-                    "\"webm[abr>0]/bestaudio/best\"".into(),
-                    "-R".into(),
-                    "infinite".into(),
-//                    "--extractor-args".into(),
-//                    "youtube:player-client=tv".into(),
-                ])
-            } else {
-                YoutubeDl::new(http_client, url.clone()).user_args(vec![
-                    "-4".into(),
-                    "-f".into(),
-                    "\"webm[abr>0]/bestaudio/best\"".into(),
-                    "-R".into(),
-                    "infinite".into(),
-//                    "--extractor-args".into(),
-//                    "youtube:player-client=tv".into(),
-                ])
-            };
+    let (src, mut metadata) = if let Some(path) = local {
+        let metadata = library::metadata(data, &path).await;
+
+        (Source::File(File::new(path)), metadata)
+    } else if let Some(probe) = &direct {
+        let mut src = Source::Http(HttpRequest::new(http_client.clone(), url.clone()));
+
+        let started = Instant::now();
+        let metadata = direct::metadata(&url, probe, &mut src).await;
+        metrics::resolved("direct", started.elapsed());
+
+        (src, metadata)
+    } else {
+        let src = if do_search {
+            YoutubeDl::new_search(http_client.clone(), url.clone()).user_args(vec![
+                "-4".into(),
+                "-f".into(),
+                settings.ytdl_format_arg(),
+                "-R".into(),
+                "infinite".into(),
+//            "--extractor-args".into(),
+//            "youtube:player-client=tv".into(),
+            ])
+        } else {
+            YoutubeDl::new(http_client.clone(), url.clone()).user_args(vec![
+                "-4".into(),
+                "-f".into(),
+                settings.ytdl_format_arg(),
+                "-R".into(),
+                "infinite".into(),
+//            "--extractor-args".into(),
+//            "youtube:player-client=tv".into(),
+            ])
+        };
+
+        let kind = if do_search { "search" } else { "url" };
+        let started = Instant::now();
+        let metadata = src
+            .clone()
+            .aux_metadata()
+            .await
+            .inspect_err(|_| metrics::resolve_failed(kind))?;
+        metrics::resolved(kind, started.elapsed());
+
+        (Source::YoutubeDl(src), metadata)
+    };
 
-            let metadata = src.clone().aux_metadata().await?.clone();
+    // The feed knows better than the tags of the file
+    if let TrackSource::Podcast(episode) = &source {
+        metadata.title = episode.title.clone().or(metadata.title);
+        metadata.artist = episode.show.clone().or(metadata.artist);
+        metadata.channel = episode.show.clone().or(metadata.channel);
+        metadata.duration = metadata.duration.or(episode.duration);
+    }
 
-            if let Some(message_id) = existing_queue.message_id {
-                existing_queue.queue.push(metadata);
+    // Livestreams have no duration and are never too long
+    if let (Some(max), Some(duration)) = (settings.max_track_duration_secs, metadata.duration)
+        && duration.as_secs() > max
+    {
+        return Err(BeatError::TrackTooLong);
+    }
 
-                let _ = ctx
-                    .http
-                    .edit_message(channel_id, message_id, &to_embed(&existing_queue), vec![])
-                    .await?;
-            } else {
-                existing_queue.queue.push(metadata);
+    let episode_url = match &source {
+        TrackSource::Podcast(episode) => Some(episode.url.clone()),
+        _ => None,
+    };
+    // Episodes resume where the guild left them
+    let start = match &episode_url {
+        Some(episode_url) => podcast::podcasts(guild_id).position(episode_url),
+        None => start_position(&url),
+    };
+    let duration = metadata.duration;
+    let source_url = metadata.source_url.clone().unwrap_or(url);
 
-                let message = ctx
-                    .http
-                    .send_message(channel_id, vec![], &to_embed(&existing_queue))
-                    .await?;
+    let mut maybe_queue = queue_lock.write().await;
 
+    let existing_queue = maybe_queue.get_mut(&guild_id).ok_or(BeatError::NoQueue)?;
+
+    // The queue may have changed while resolving
+    admit(existing_queue, &settings)?;
+
+    // Nothing to insert before while the queue is empty
+    let index = match position {
+        Some(position) if !existing_queue.queue.is_empty() => {
+            (existing_queue.playing_index + position).min(existing_queue.queue.len())
+        }
+        _ => existing_queue.queue.len(),
+    };
+    let appended = index == existing_queue.queue.len();
+    let mut queued = QueuedTrack::new(metadata, requester, source);
+    queued.direct = direct.is_some();
+    existing_queue.queue.insert(index, queued);
+
+    // The track is in the queue already, it has to reach Songbird even if Discord refuses the
+    // message
+    if existing_queue.message_id.is_some() {
+        update_message(http, existing_queue, &settings)
+            .await
+            .map_err(|error| warn!(%error, "Failed to update queue message"))
+            .unwrap_or_default();
+    } else {
+        let channel_id = settings.music_channel.unwrap_or(channel_id);
+        match http
+            .send_message(channel_id, vec![], &to_embed(existing_queue, &settings))
+            .await
+        {
+            Ok(message) => {
+                existing_queue.channel_id = Some(channel_id);
                 existing_queue.message_id = Some(message.id);
             }
+            Err(error) => {
+                metrics::discord_error();
+                warn!(%error, "Failed to send queue message");
+            }
+        }
+    }
 
-            // Attach an event handler to see notifications of all track errors.
-            let mut handler = handler_lock.lock().await;
+    metrics::queue_length(guild_id, existing_queue.queue.len());
+    publish(data, guild_id, existing_queue).await;
 
-            handler.enqueue_with_preload(src.into(), Duration::from_secs(10).into());
+    // Attach an event handler to see notifications of all track errors.
+    let mut handler = handler_lock.lock().await;
 
-            if should_delete {
-                if let Interaction::Command(command) = interaction {
-                    // Delete ephemeral response
-                    command.delete_response(ctx).await?;
-                    return Ok(false);
-                }
+    let track = handler.enqueue_with_preload(
+        loudness::track(
+            filters::input(src.clone(), existing_queue.filters.clone()),
+            &settings,
+        ),
+        settings.preload(duration).into(),
+    );
+    crossfade::watch(&track, data.clone(), guild_id);
+
+    if !appended {
+        // Songbird's queue starts at the current track
+        let at = index - existing_queue.playing_index;
+        handler
+            .queue()
+            .modify_queue(|queue| move_last(queue, 1, at));
+    }
 
-                return Ok(should_delete);
-            }
+    if let Some(start) = start {
+        // Applied once the track is ready, a failure only means it plays from the start
+        drop(track.seek(start));
+    }
+    if let Some(episode_url) = episode_url {
+        podcast::watch(guild_id, track.clone(), episode_url, start, duration);
+    }
+    // Radios never end, their loudness cannot be measured
+    if direct.is_some_and(|probe| probe.live) {
+        icy::watch(
+            data.clone(),
+            http.clone(),
+            guild_id,
+            track,
+            source_url,
+            http_client,
+        );
+    } else {
+        loudness::normalize(data.clone(), &settings, source_url, src, track);
+    }
 
-            return Ok(should_delete);
-        }
+    Ok(())
+}
 
+/// Whether `queue` takes one more track, refused while stopping or once the guild's limit is
+/// reached.
+fn admit(queue: &Queue, settings: &Settings) -> Result<(), BeatError> {
+    if queue.stopping {
         return Err(BeatError::Stopping);
     }
 
-    Ok(should_delete)
+    // Only the current and upcoming tracks count, history is not capped
+    let queued = queue.queue.len() - queue.playing_index.min(queue.queue.len());
+    if settings.max_queue_length.is_some_and(|max| queued >= max) {
+        return Err(BeatError::QueueFull);
+    }
+
+    Ok(())
+}
+
+/// Appends a track or playlist to a guild that already has an active session, posting to the
+/// channel of its queue message. Used by callers that have no interaction to answer.
+pub async fn append_to_session(
+    data: &Arc<RwLock<TypeMap>>,
+    http: &Arc<Http>,
+    guild_id: GuildId,
+    url: String,
+) -> Result<(), BeatError> {
+    let manager = songbird_manager(data).await?;
+    let handler_lock = manager.get(guild_id).ok_or(BeatError::NoManager)?;
+
+    let channel_id = {
+        let queue_lock = {
+            let guard = data.read().await;
+            guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
+        };
+        let maybe_queue = queue_lock.read().await;
+        maybe_queue
+            .get(&guild_id)
+            .ok_or(BeatError::NoQueue)?
+            .channel_id
+            .ok_or(BeatError::NoManager)?
+    };
+
+    let http_client = {
+        let guard = data.read().await;
+        guard.get::<HttpKey>().cloned().ok_or(BeatError::NoHttp)?
+    };
+
+    let (tracks, source) = resolve_tracks(url, &http_client).await?;
+
+    for track in tracks {
+        let request = TrackRequest {
+            guild_id,
+            channel_id,
+            url: track,
+            requester: None,
+            source: source.clone(),
+            position: None,
+        };
+
+        enqueue(
+            data,
+            http,
+            handler_lock.clone(),
+            request,
+            http_client.clone(),
+        )
+        .await
+        // Ignore error in a playlist, keep loading next ones
+        .map_err(|error| warn!(%error, "Failed to enqueue track"))
+        .unwrap_or_default();
+    }
+
+    Ok(())
+}
+
+/// Songbird manager stored in the shared data, for callers without a serenity [`Context`].
+pub async fn songbird_manager(data: &Arc<RwLock<TypeMap>>) -> Result<Arc<Songbird>, BeatError> {
+    let guard = data.read().await;
+    guard
+        .get::<SongbirdKey>()
+        .cloned()
+        .ok_or(BeatError::NoSongbird)
 }
 
 #[async_trait]
@@ -342,10 +675,10 @@ impl EventHandler for TrackErrorNotifier {
     async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
         if let EventContext::Track(track_list) = ctx {
             for (state, handle) in *track_list {
-                println!(
-                    "Track {:?} encountered an error: {:?}",
-                    handle.uuid(),
-                    state.playing
+                warn!(
+                    track = %handle.uuid(),
+                    state = ?state.playing,
+                    "Track encountered an error"
                 );
             }
         }
@@ -357,7 +690,19 @@ impl EventHandler for TrackErrorNotifier {
 #[async_trait]
 impl EventHandler for OnTrackEnd {
     async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
-        if let EventContext::Track(_) = ctx {
+        let span = logging::track_event_span("end", self.guild_id, self.channel_id);
+
+        self.on_end(ctx).instrument(span).await
+    }
+}
+
+impl OnTrackEnd {
+    async fn on_end(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(tracks) = ctx {
+            history::ended(&self.data, tracks).await;
+
+            let settings = settings(&self.data, self.guild_id).await;
+
             let queue_lock = {
                 let guard = self.data.read().await;
                 guard.get::<QueueKey>().unwrap().clone()
@@ -366,25 +711,38 @@ impl EventHandler for OnTrackEnd {
             let mut maybe_queue = queue_lock.write().await;
 
             if let Some(existing_queue) = maybe_queue.get_mut(&self.guild_id) {
-                println!("Queue exists: {:?}", existing_queue);
+                debug!(
+                    playing_index = existing_queue.playing_index,
+                    tracks = existing_queue.queue.len(),
+                    "Track ended"
+                );
 
-                if existing_queue.is_last() {
-                    println!("Was the last song, should leave voice channel");
-                    if let Some(message_id) = existing_queue.message_id {
-                        println!("Emptying tracklist for guild {}", self.guild_id);
+                let faded_out = existing_queue.crossfading.as_ref().is_some_and(|outgoing| {
+                    tracks.iter().any(|(_, track)| track.uuid() == outgoing.uuid())
+                });
 
-                        println!(
-                            "Deleting message at channel {} with ID {}",
-                            self.channel_id, message_id
-                        );
+                if faded_out {
+                    // The next track is already playing and counted as the current one
+                    existing_queue.crossfading = None;
+                    publish(&self.data, self.guild_id, existing_queue).await;
+                } else if existing_queue.is_last() {
+                    info!("Was the last track, leaving voice channel");
+                    if let Some(message_id) = existing_queue.message_id {
+                        debug!(%message_id, "Deleting queue message");
 
                         self.http
-                            .delete_message(self.channel_id, message_id, Some("Tracklist ended"))
+                            .delete_message(
+                                existing_queue.channel_id.unwrap_or(self.channel_id),
+                                message_id,
+                                Some("Tracklist ended"),
+                            )
                             .await
+                            .map_err(|error| {
+                                metrics::discord_error();
+                                warn!(?error, "Failed to delete queue message")
+                            })
                             .unwrap_or_default();
 
-                        println!("Tracklist removed for guild {:?}", self.guild_id);
-
                         // Disconnect and clear Songbird for the guild
                         let manager_lock = {
                             let guard = self.data.read().await;
@@ -399,44 +757,55 @@ impl EventHandler for OnTrackEnd {
                             .await
                             .queue()
                             .stop();
-                        manager_lock.remove(self.guild_id).await.unwrap();
+                        if settings.idle_timeout_secs == 0 {
+                            manager_lock.remove(self.guild_id).await.unwrap();
+                            metrics::voice_disconnected();
+                        } else {
+                            leave_when_idle(
+                                self.data.clone(),
+                                self.guild_id,
+                                Duration::from_secs(settings.idle_timeout_secs),
+                            );
+                        }
+                        metrics::queue_length(self.guild_id, 0);
 
-                        println!("Tracklist removed for guild {:?}", self.guild_id);
+                        debug!("Tracklist removed");
 
                         // Remove local data
-                        maybe_queue
-                            .get_mut(&self.guild_id)
-                            .ok_or(BeatError::NoQueue)
-                            .unwrap_or(&mut Queue::default())
-                            .reset_for_play();
+                        if let Some(queue) = maybe_queue.get_mut(&self.guild_id) {
+                            queue.reset_for_play();
+                            publish(&self.data, self.guild_id, queue).await;
+                        }
+                    }
+                } else if let Some(existing_queue) = maybe_queue.get_mut(&self.guild_id) {
+                    if !existing_queue.did_skip {
+                        existing_queue.playing_index += 1;
                     }
-                } else {
-                    println!("Not the last sound, should increment playing index");
+                    existing_queue.did_skip = false;
+                    existing_queue.repeat = false;
+                    existing_queue.pause = false;
+                    existing_queue.position = Duration::ZERO;
 
-                    if let Some(existing_queue) = maybe_queue.get_mut(&self.guild_id) {
-                        if !existing_queue.did_skip {
-                            existing_queue.playing_index = existing_queue.playing_index + 1;
-                        }
-                        existing_queue.did_skip = false;
-                        existing_queue.repeat = false;
-                        existing_queue.pause = false;
+                    let manager_lock = {
+                        let guard = self.data.read().await;
+                        guard.get::<SongbirdKey>().unwrap().clone()
+                    };
 
-                        let manager_lock = {
-                            let guard = self.data.read().await;
-                            guard.get::<SongbirdKey>().unwrap().clone()
-                        };
+                    manager_lock
+                        .get(self.guild_id)
+                        .unwrap()
+                        .lock()
+                        .await
+                        .queue()
+                        .resume()
+                        .unwrap();
 
-                        manager_lock
-                            .get(self.guild_id)
-                            .unwrap()
-                            .lock()
-                            .await
-                            .queue()
-                            .resume()
-                            .unwrap();
+                    debug!(
+                        playing_index = existing_queue.playing_index,
+                        "Playlist index incremented"
+                    );
 
-                        println!("Playlist index incremented: {:?}", existing_queue);
-                    }
+                    publish(&self.data, self.guild_id, existing_queue).await;
                 }
             }
         }
@@ -447,8 +816,18 @@ impl EventHandler for OnTrackEnd {
 #[async_trait]
 impl EventHandler for OnTrackStart {
     async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
-        if let EventContext::Track(_) = ctx {
-            println!("New track playing, updating the queue");
+        let span = logging::track_event_span("start", self.guild_id, self.channel_id);
+
+        self.on_start(ctx).instrument(span).await
+    }
+}
+
+impl OnTrackStart {
+    async fn on_start(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(tracks) = ctx {
+            metrics::track_played();
+
+            let settings = settings(&self.data, self.guild_id).await;
 
             let queue_lock = {
                 let guard = self.data.write().await;
@@ -458,66 +837,120 @@ impl EventHandler for OnTrackStart {
             let mut maybe_queue = queue_lock.write().await;
 
             if let Some(existing_queue) = maybe_queue.get_mut(&self.guild_id) {
-                println!("Queue exists: {:?}", existing_queue);
-
-                if let Some(message_id) = existing_queue.message_id {
-                    println!(
-                        "Message exists: {:?} in channel {}",
-                        message_id, self.channel_id
-                    );
+                info!(
+                    playing_index = existing_queue.playing_index,
+                    tracks = existing_queue.queue.len(),
+                    "New track playing, updating the queue"
+                );
 
-                    self.http
-                        .edit_message(
-                            self.channel_id,
-                            message_id,
-                            &to_embed(existing_queue),
-                            vec![],
-                        )
-                        .await
-                        .unwrap_or_default();
+                existing_queue.position = Duration::ZERO;
+                existing_queue.skip_votes.clear();
 
-                    println!(
-                        "Edited message: {:?} in channel {}",
-                        message_id, self.channel_id
-                    )
+                if let (Some((_, track)), Some(queued)) = (
+                    tracks.first(),
+                    existing_queue.queue.get(existing_queue.playing_index),
+                ) {
+                    history::started(&self.data, self.guild_id, track, queued).await;
                 }
+
+                autoplay::queue_related(
+                    self.data.clone(),
+                    self.http.clone(),
+                    self.guild_id,
+                    existing_queue.channel_id.unwrap_or(self.channel_id),
+                    existing_queue,
+                    &settings,
+                );
+
+                update_message(&self.http, existing_queue, &settings)
+                    .await
+                    .map_err(|error| warn!(?error, "Failed to edit queue message"))
+                    .unwrap_or_default();
+
+                publish(&self.data, self.guild_id, existing_queue).await;
             }
         }
         None
     }
 }
 
-pub async fn ytdl_playlist(uri: String) -> Option<Vec<String>> {
+/// Leaves the voice channel after `timeout`, unless something was queued in the meantime.
+fn leave_when_idle(data: Arc<RwLock<TypeMap>>, guild_id: GuildId, timeout: Duration) {
+    tokio::spawn(
+        async move {
+            tokio::time::sleep(timeout).await;
+
+            let queue_lock = {
+                let guard = data.read().await;
+                guard.get::<QueueKey>().cloned()
+            };
+            let Some(queue_lock) = queue_lock else {
+                return;
+            };
+
+            let idle = queue_lock
+                .read()
+                .await
+                .get(&guild_id)
+                .is_none_or(|queue| queue.queue.is_empty());
+
+            if idle
+                && let Ok(manager) = songbird_manager(&data).await
+                && manager.get(guild_id).is_some()
+            {
+                info!("Idle timeout reached, leaving voice channel");
+                manager.remove(guild_id).await.unwrap_or_default();
+                metrics::voice_disconnected();
+            }
+        }
+        .instrument(info_span!("idle_timeout", guild_id = guild_id.get())),
+    );
+}
+
+/// Links of the entries of a playlist, along with its title when yt-dlp reports one.
+pub async fn ytdl_playlist(uri: String) -> Option<(Option<String>, Vec<String>)> {
     let args = vec![uri.as_str(), "-4", "--flat-playlist", "-j"];
 
-    let mut child = Command::new("yt-dlp")
+    let started = Instant::now();
+    let output = Command::new("yt-dlp")
         .args(args)
         .stdout(Stdio::piped())
-        .spawn()
-        .unwrap();
-
-    let Some(stdout) = &mut child.stdout else {
-        return None;
-    };
-
-    let reader = BufReader::new(stdout);
+        .kill_on_drop(true)
+        .output()
+        .await
+        .inspect_err(|_| metrics::resolve_failed("playlist"))
+        .ok()?;
+    metrics::resolved("playlist", started.elapsed());
 
-    let lines = reader.lines().map_while(Result::ok).map(|line| {
-        let entry: Value = serde_json::from_str(&line).unwrap();
-        entry
-            .get("webpage_url")
-            .unwrap()
-            .as_str()
-            .unwrap()
-            .to_string()
-    });
+    Some(parse_playlist(&String::from_utf8_lossy(&output.stdout)))
+}
 
-    Some(lines.collect())
+/// Reads the JSON lines yt-dlp prints for a flat playlist, skipping the entries without a link.
+fn parse_playlist(output: &str) -> (Option<String>, Vec<String>) {
+    let entries: Vec<Value> = output
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+
+    let title = entries
+        .first()
+        .and_then(|entry| entry.get("playlist_title"))
+        .and_then(Value::as_str)
+        .map(String::from);
+
+    let links = entries
+        .iter()
+        .filter_map(|entry| entry.get("webpage_url").and_then(Value::as_str))
+        .map(String::from)
+        .collect();
+
+    (title, links)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use songbird::input::AuxMetadata;
 
     #[tokio::test]
     async fn it_works() {
@@ -529,4 +962,49 @@ mod tests {
 
         println!("{:#?}", src);
     }
+
+    #[test]
+    fn parses_playlist_entries_with_a_link() {
+        let output = concat!(
+            r#"{"webpage_url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ", "playlist_title": "Mix"}"#,
+            "\n",
+            r#"{"title": "Private video", "playlist_title": "Mix"}"#,
+            "\n",
+            "not json\n",
+            r#"{"webpage_url": "https://www.youtube.com/watch?v=yPYZpwSpKmA"}"#,
+        );
+
+        let (title, links) = parse_playlist(output);
+
+        assert_eq!(title.as_deref(), Some("Mix"));
+        assert_eq!(
+            links,
+            [
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+                "https://www.youtube.com/watch?v=yPYZpwSpKmA"
+            ]
+        );
+    }
+
+    #[test]
+    fn admits_until_the_limit_of_upcoming_tracks() {
+        let settings = Settings {
+            max_queue_length: Some(2),
+            ..Settings::default()
+        };
+        let mut queue = Queue::default();
+        queue.reset_for_play();
+        let track = || QueuedTrack::new(AuxMetadata::default(), None, TrackSource::Url);
+
+        queue.queue = vec![track(), track()];
+        assert!(matches!(admit(&queue, &settings), Err(BeatError::QueueFull)));
+
+        // Played tracks do not count
+        queue.queue.push(track());
+        queue.playing_index = 2;
+        assert!(admit(&queue, &settings).is_ok());
+
+        queue.stopping = true;
+        assert!(matches!(admit(&queue, &settings), Err(BeatError::Stopping)));
+    }
 }
@@ -0,0 +1,33 @@
+use crate::commands::play::play;
+use crate::errors::errors::BeatError;
+use serenity::all::Interaction;
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::client::Context;
+use serenity::model::application::{CommandOptionType, ResolvedOption, ResolvedValue};
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("playnext")
+        .description("Play a track right after the current one")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "track",
+                "The track to play next",
+            )
+            .required(true)
+            .set_autocomplete(true),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &Interaction,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BeatError> {
+    let url = options.iter().find_map(|option| match option.value {
+        ResolvedValue::String(url) if option.name == "track" => Some(url),
+        _ => None,
+    });
+
+    play(ctx, interaction, url, Some(1)).await
+}
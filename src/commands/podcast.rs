@@ -0,0 +1,274 @@
+use crate::commands::play::play_from;
+use crate::errors::errors::BeatError;
+use crate::messages::messages::to_podcast_episodes;
+use crate::podcast::podcast::{Episode, Subscription, fetch, podcasts, short_id, update};
+use crate::{HttpKey, TrackSource};
+use reqwest::Client;
+use serde_json::json;
+use serenity::all::{ComponentInteractionDataKind, Interaction};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::client::Context;
+use serenity::model::application::{CommandOptionType, ResolvedOption, ResolvedValue};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Pickers left unused for longer than this are closed.
+const PICK_TIMEOUT_SECS: u64 = 300;
+const EXPIRED: &str = "_This list expired, use `/podcast episodes` again._";
+const NO_EPISODES: &str = "_No episodes to play, follow a podcast with `/podcast subscribe`._";
+
+pub fn register() -> CreateCommand {
+    let subcommand = |name: &str, description: &str| {
+        CreateCommandOption::new(CommandOptionType::SubCommand, name, description)
+    };
+    let podcast = || {
+        CreateCommandOption::new(
+            CommandOptionType::String,
+            "podcast",
+            "Part of the podcast name",
+        )
+    };
+
+    CreateCommand::new("podcast")
+        .description("Plays podcasts, resuming episodes where the server left them")
+        .add_option(
+            subcommand("subscribe", "Follows a podcast by its RSS or Atom feed").add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "url", "Link of the feed")
+                    .required(true),
+            ),
+        )
+        .add_option(
+            subcommand("unsubscribe", "Stops following a podcast")
+                .add_sub_option(podcast().required(true)),
+        )
+        .add_option(
+            subcommand(
+                "latest",
+                "Queues the latest episode of the followed podcasts",
+            )
+            .add_sub_option(podcast()),
+        )
+        .add_option(
+            subcommand("episodes", "Lists the latest episodes to pick one")
+                .add_sub_option(podcast()),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &Interaction,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BeatError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let http_client = {
+        let data = ctx.data.read().await;
+        data.get::<HttpKey>().cloned().ok_or(BeatError::NoHttp)?
+    };
+
+    if let Interaction::Command(command) = interaction {
+        command.defer_ephemeral(ctx).await?;
+        let guild_id = command.guild_id.ok_or(BeatError::NoGuild)?;
+
+        let Some(ResolvedOption {
+            name,
+            value: ResolvedValue::SubCommand(arguments),
+            ..
+        }) = options.first()
+        else {
+            return Err(BeatError::NoValidCommand);
+        };
+        let string = |name: &str| {
+            arguments.iter().find_map(|option| match option.value {
+                ResolvedValue::String(value) if option.name == name => Some(value),
+                _ => None,
+            })
+        };
+
+        let podcasts = podcasts(guild_id);
+        let subscriptions = podcasts.find(string("podcast"));
+
+        let content = match *name {
+            "subscribe" => {
+                let url = string("url").ok_or(BeatError::NoValidCommand)?;
+                let Some(feed) = fetch(&http_client, url).await else {
+                    return answer(ctx, &command.token, "_That link is not a podcast feed._").await;
+                };
+                let title = feed.title.unwrap_or(String::from(url));
+
+                let subscription = Subscription {
+                    url: String::from(url),
+                    title: title.clone(),
+                };
+                update(guild_id, |podcasts| {
+                    podcasts.subscriptions.retain(|other| other.url != url);
+                    podcasts.subscriptions.push(subscription);
+                })?;
+
+                json!({
+                    "content": format!(
+                        "Subscribed to **{}**, {} episodes.",
+                        title,
+                        feed.episodes.len()
+                    )
+                })
+            }
+            "unsubscribe" => {
+                let Some(subscription) = subscriptions.first() else {
+                    return answer(ctx, &command.token, "_No such podcast._").await;
+                };
+                update(guild_id, |podcasts| {
+                    podcasts
+                        .subscriptions
+                        .retain(|other| other.url != subscription.url)
+                })?;
+
+                json!({ "content": format!("Unsubscribed from **{}**.", subscription.title) })
+            }
+            "latest" => {
+                let Some((_, episode)) = latest(&http_client, &subscriptions)
+                    .await
+                    .into_iter()
+                    .next()
+                else {
+                    return answer(ctx, &command.token, NO_EPISODES).await;
+                };
+
+                // Answers like `/play`, the queue message tells the rest
+                return play_from(
+                    ctx,
+                    interaction,
+                    Some(&episode.url.clone()),
+                    Some(TrackSource::Podcast(episode)),
+                    None,
+                )
+                .await;
+            }
+            "episodes" => {
+                let episodes = latest(&http_client, &subscriptions).await;
+                let episodes: Vec<(&Subscription, &Episode, _)> = episodes
+                    .iter()
+                    .map(|(subscription, episode)| {
+                        (*subscription, episode, podcasts.position(&episode.url))
+                    })
+                    .collect();
+
+                to_podcast_episodes(&episodes, now)
+            }
+            _ => return Err(BeatError::NoValidCommand),
+        };
+
+        ctx.http
+            .edit_original_interaction_response(&command.token, &content, vec![])
+            .await?;
+    } else if let Interaction::Component(component) = interaction {
+        let guild_id = component.guild_id.ok_or(BeatError::NoGuild)?;
+
+        // podcast:<issued>, valued <feed id>:<episode id>
+        let issued = match component.data.custom_id.split(':').collect::<Vec<_>>()[..] {
+            ["podcast", issued] => issued.parse::<u64>().unwrap_or(0),
+            _ => return Err(BeatError::NoValidCommand),
+        };
+        let (feed_id, episode_id) = match &component.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => {
+                values.first().and_then(|value| value.split_once(':'))
+            }
+            _ => None,
+        }
+        .ok_or(BeatError::NoValidCommand)?;
+
+        let podcasts = podcasts(guild_id);
+        let subscription = podcasts
+            .subscriptions
+            .iter()
+            .find(|subscription| short_id(&subscription.url) == feed_id)
+            .filter(|_| now.saturating_sub(issued) <= PICK_TIMEOUT_SECS);
+        let Some(subscription) = subscription else {
+            ctx.http
+                .create_interaction_response(
+                    component.id,
+                    &component.token,
+                    &json!({"type": 7, "data": {"content": EXPIRED, "components": []}}),
+                    vec![],
+                )
+                .await?;
+            return Ok(());
+        };
+
+        // Drop the menu right away so the episode can't be picked twice
+        ctx.http
+            .create_interaction_response(
+                component.id,
+                &component.token,
+                &json!({"type": 7, "data": {"content": "_Adding the episode..._", "components": []}}),
+                vec![],
+            )
+            .await?;
+
+        let episode = fetch(&http_client, &subscription.url)
+            .await
+            .and_then(|feed| {
+                feed.episodes
+                    .into_iter()
+                    .find(|episode| short_id(&episode.url) == episode_id)
+            });
+        let result = match episode {
+            Some(episode) => {
+                let url = episode.url.clone();
+                play_from(
+                    ctx,
+                    interaction,
+                    Some(&url),
+                    Some(TrackSource::Podcast(episode)),
+                    None,
+                )
+                .await
+            }
+            None => Err(BeatError::Other("The episode is no longer in the feed")),
+        };
+        let content = match &result {
+            Ok(()) => String::from("Added to the queue."),
+            Err(error) => format!("_{}_", error),
+        };
+        ctx.http
+            .edit_original_interaction_response(
+                &component.token,
+                &json!({ "content": content }),
+                vec![],
+            )
+            .await?;
+
+        result?;
+    }
+
+    Ok(())
+}
+
+async fn answer(ctx: &Context, token: &str, content: &str) -> Result<(), BeatError> {
+    ctx.http
+        .edit_original_interaction_response(token, &json!({ "content": content }), vec![])
+        .await?;
+    Ok(())
+}
+
+/// Episodes of the given podcasts, newest first across them. Feeds that fail to load are left
+/// out.
+async fn latest<'a>(
+    client: &Client,
+    subscriptions: &[&'a Subscription],
+) -> Vec<(&'a Subscription, Episode)> {
+    let mut episodes = vec![];
+    for subscription in subscriptions {
+        if let Some(feed) = fetch(client, &subscription.url).await {
+            episodes.extend(
+                feed.episodes
+                    .into_iter()
+                    .map(|episode| (*subscription, episode)),
+            );
+        }
+    }
+
+    episodes.sort_by_key(|(_, episode)| std::cmp::Reverse(episode.published));
+    episodes
+}
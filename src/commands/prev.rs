@@ -1,10 +1,20 @@
+use crate::audio::source::Source;
+use crate::audio::{crossfade, filters, loudness};
+use crate::commands::play::songbird_manager;
 use crate::errors::errors::BeatError;
-use crate::{HttpKey, QueueKey};
-use serenity::all::Interaction;
+use crate::library::library::local_path;
+use crate::podcast::podcast;
+use crate::settings::settings::{Settings, settings};
+use crate::{HttpKey, QueueKey, TrackSource};
+use reqwest::Client as HttpClient;
+use serenity::all::{GuildId, Interaction};
 use serenity::builder::CreateCommand;
 use serenity::client::Context;
-use songbird::input::YoutubeDl;
-use std::time::Duration;
+use serenity::prelude::TypeMap;
+use songbird::input::{File, HttpRequest, YoutubeDl};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::RwLock;
 
 pub fn register() -> CreateCommand {
     CreateCommand::new("prev").description("Plays the previous song")
@@ -21,189 +31,215 @@ pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatErr
     } else {
         None
     } {
+        previous(&ctx.data, guild_id).await?;
+    }
+
+    if let Interaction::Command(command) = interaction {
+        // Delete ephemeral response
+        command.delete_response(ctx).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn previous(data: &Arc<RwLock<TypeMap>>, guild_id: GuildId) -> Result<(), BeatError> {
+    let playing_index = {
         let queue_lock = {
-            let guard = ctx.data.read().await;
+            let guard = data.read().await;
             guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
         };
+        let maybe_queue = queue_lock.read().await;
+        match maybe_queue.get(&guild_id) {
+            Some(existing_queue) if !existing_queue.queue.is_empty() => {
+                existing_queue.playing_index
+            }
+            Some(_) => return Err(BeatError::NoPreviousTrack),
+            None => return Ok(()),
+        }
+    };
 
-        let mut maybe_queue = queue_lock.write().await;
-
-        if let Some(existing_queue) = maybe_queue.get_mut(&guild_id) {
-            if existing_queue.playing_index >= 1 {
-                let current_metadata = existing_queue
-                    .queue
-                    .get(existing_queue.playing_index)
-                    .ok_or(BeatError::NoCurrentTrack)?;
-
-                existing_queue.playing_index = existing_queue.playing_index.saturating_sub(1);
-
-                let previous_metadata = existing_queue
-                    .queue
-                    .get(existing_queue.playing_index)
-                    .ok_or(BeatError::NoPreviousTrack)?;
-
-                let manager = songbird::get(ctx).await.ok_or(BeatError::NoSongbird)?;
-                let handler_lock = manager.get(guild_id).ok_or(BeatError::NoManager)?;
-
-                let http_client = {
-                    let data = ctx.data.read().await;
-                    data.get::<HttpKey>().cloned().ok_or(BeatError::NoHttp)?
-                };
-
-                let src_previous = {
-                    YoutubeDl::new(
-                        http_client.clone(),
-                        previous_metadata
-                            .clone()
-                            .source_url
-                            .ok_or(BeatError::NoPreviousSourceUrl)?,
-                    )
-                    .user_args(vec![
-                        "-j".into(),
-                        "-4".into(),
-                        "-q".into(),
-                        "--no-simulate".into(),
-                        "-f".into(),
-                        "\"webm[abr>0]/bestaudio/best\"".into(),
-                        "-R".into(),
-                        "infinite".into(),
-                        "--ignore-config".into(),
-                        "--no-warnings".into(),
-//                        "--extractor-args".into(),
-//                        "youtube:player-client=tv".into(),
-                        "--cache-dir".into(),
-                        "./yt-dlp-cache".into(),
-                    ])
-                };
-
-                let src = {
-                    YoutubeDl::new(
-                        http_client.clone(),
-                        current_metadata
-                            .clone()
-                            .source_url
-                            .ok_or(BeatError::NoCurrentSourceUrl)?,
-                    )
-                    .user_args(vec![
-                        "-j".into(),
-                        "-4".into(),
-                        "-q".into(),
-                        "--no-simulate".into(),
-                        "-f".into(),
-                        "\"webm[abr>0]/bestaudio/best\"".into(),
-                        "-R".into(),
-                        "infinite".into(),
-                        "--ignore-config".into(),
-                        "--no-warnings".into(),
-//                        "--extractor-args".into(),
-//                        "youtube:player-client=tv".into(),
-                        "--cache-dir".into(),
-                        "./yt-dlp-cache".into(),
-                    ])
-                };
-
-                // Skips the track
-                {
-                    let mut handle = handler_lock.lock().await;
-
-                    // Place the previous track at the end
-                    handle
-                        .enqueue_with_preload(src_previous.into(), Duration::from_secs(15).into());
-                    // Place the current track at the end
-                    handle.enqueue_with_preload(src.into(), Duration::from_secs(15).into());
-
-                    handle.queue().modify_queue(|queue| {
-                        // Get the current track
-                        let current = queue.pop_back().expect("Just pushed, can not fail");
-                        // Get the previous track
-                        let previous = queue.pop_back().expect("Just pushed, can not fail");
-
-                        // Put the current at the beginning
-                        queue.insert(1, current);
-                        // Put the previous before the current one
-                        queue.insert(1, previous);
-                    });
-
-                    // Skips the current track which is outdated, to play the previous one
-                    if existing_queue.playing_index.checked_sub(1).is_none() {
-                        existing_queue.playing_index = 0;
-                        existing_queue.did_skip = true;
-                    } else {
-                        existing_queue.playing_index = existing_queue.playing_index - 1;
+    // On the first track, restart it
+    jump_to(data, guild_id, playing_index.saturating_sub(1)).await
+}
+
+/// Makes `target` the playing track.
+///
+/// Songbird drops tracks once played, so going backwards recreates every track from `target` up
+/// to the current one and places them right after it, while going forwards drops the tracks in
+/// between. The outdated current track is then skipped.
+pub async fn jump_to(
+    data: &Arc<RwLock<TypeMap>>,
+    guild_id: GuildId,
+    target: usize,
+) -> Result<(), BeatError> {
+    let settings = settings(data, guild_id).await;
+    let queue_lock = {
+        let guard = data.read().await;
+        guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
+    };
+
+    let mut maybe_queue = queue_lock.write().await;
+
+    if let Some(existing_queue) = maybe_queue.get_mut(&guild_id) {
+        let playing_index = existing_queue.playing_index;
+
+        if target >= existing_queue.queue.len() {
+            return Err(BeatError::NoSuchTrack);
+        }
+
+        let manager = songbird_manager(data).await?;
+        let handler_lock = manager.get(guild_id).ok_or(BeatError::NoManager)?;
+
+        if target > playing_index {
+            let handle = handler_lock.lock().await;
+
+            handle.queue().modify_queue(|queue| {
+                // Drop the tracks between the current one and the target
+                if queue.len() > 1 {
+                    let end = (target - playing_index).min(queue.len());
+                    for skipped in queue.drain(1..end) {
+                        let _ = skipped.stop();
                     }
-                    handle.queue().skip().expect("Just pushed, can not fail");
                 }
-            } else {
-                let current_metadata = existing_queue
-                    .queue
-                    .get(existing_queue.playing_index)
-                    .ok_or(BeatError::NoCurrentTrack)?;
-
-                let http_client = {
-                    let data = ctx.data.read().await;
-                    data.get::<HttpKey>().cloned().ok_or(BeatError::NoHttp)?
-                };
-
-                let src = {
-                    YoutubeDl::new(
-                        http_client.clone(),
-                        current_metadata
-                            .clone()
-                            .source_url
-                            .ok_or(BeatError::NoCurrentSourceUrl)?,
-                    )
-                    .user_args(vec![
-                        "-j".into(),
-                        "-4".into(),
-                        "-q".into(),
-                        "--no-simulate".into(),
-                        "-f".into(),
-                        "\"webm[abr>0]/bestaudio/best\"".into(),
-                        "-R".into(),
-                        "infinite".into(),
-                        "--ignore-config".into(),
-                        "--no-warnings".into(),
-//                        "--extractor-args".into(),
-//                        "youtube:player-client=tv".into(),
-                        "--cache-dir".into(),
-                        "./yt-dlp-cache".into(),
-                    ])
-                };
-
-                let manager = songbird::get(ctx).await.ok_or(BeatError::NoSongbird)?;
-                let handler_lock = manager.get(guild_id).ok_or(BeatError::NoManager)?;
-
-                {
-                    let mut handle = handler_lock.lock().await;
-
-                    // Place the current track at the end
-                    handle.enqueue_with_preload(src.into(), Duration::from_secs(15).into());
-
-                    handle.queue().modify_queue(|queue| {
-                        // Get the current track
-                        let current = queue.pop_back().expect("Just pushed, can not fail");
-
-                        // Put the current at the beginning
-                        queue.insert(1, current);
-                    });
-
-                    // Skips the current track which is outdated, to play the previous one
-                    if existing_queue.playing_index.checked_sub(1).is_none() {
-                        existing_queue.playing_index = 0;
-                        existing_queue.did_skip = true;
-                    } else {
-                        existing_queue.playing_index = existing_queue.playing_index - 1;
+            });
+
+            existing_queue.playing_index = target - 1;
+            handle.queue().skip()?;
+        } else {
+            let http_client = {
+                let data = data.read().await;
+                data.get::<HttpKey>().cloned().ok_or(BeatError::NoHttp)?
+            };
+
+            let sources = existing_queue.queue[target..=playing_index]
+                .iter()
+                .enumerate()
+                .map(|(offset, track)| {
+                    track
+                        .metadata
+                        .source_url
+                        .clone()
+                        .map(|url| {
+                            let src = if let Some(path) = local_path(&url) {
+                                Source::File(File::new(path))
+                            } else if track.direct {
+                                Source::Http(HttpRequest::new(http_client.clone(), url.clone()))
+                            } else {
+                                Source::YoutubeDl(ytdl_source(
+                                    http_client.clone(),
+                                    url.clone(),
+                                    &settings,
+                                ))
+                            };
+                            let episode = match &track.source {
+                                TrackSource::Podcast(episode) => Some(episode.url.clone()),
+                                _ => None,
+                            };
+                            (url, src, track.metadata.duration, track.direct, episode)
+                        })
+                        .ok_or(if target + offset == playing_index {
+                            BeatError::NoCurrentSourceUrl
+                        } else {
+                            BeatError::NoPreviousSourceUrl
+                        })
+                })
+                .collect::<Result<Vec<_>, BeatError>>()?;
+            let count = sources.len();
+
+            let mut handle = handler_lock.lock().await;
+
+            // Episodes resume where the guild left them, as when first queued
+            let podcasts = podcast::podcasts(guild_id);
+
+            // Place the recreated tracks at the end
+            for (url, src, duration, direct, episode) in sources {
+                let track = handle.enqueue_with_preload(
+                    loudness::track(
+                        filters::input(src.clone(), existing_queue.filters.clone()),
+                        &settings,
+                    ),
+                    settings.rebuild_preload(duration).into(),
+                );
+                crossfade::watch(&track, data.clone(), guild_id);
+                if let Some(episode) = episode {
+                    let resumed = podcasts.position(&episode);
+                    if let Some(resumed) = resumed {
+                        // Applied once the track is ready, or it plays from the start
+                        drop(track.seek(resumed));
                     }
-                    handle.queue().skip().expect("Just pushed, can not fail");
+                    podcast::watch(guild_id, track.clone(), episode, resumed, duration);
                 }
+                // Radios have no duration and never end
+                if !direct || duration.is_some() {
+                    loudness::normalize(data.clone(), &settings, url, src, track);
+                }
+            }
+
+            // Move them right after the current track
+            handle
+                .queue()
+                .modify_queue(|queue| move_last(queue, count, 1));
+
+            // Skips the current track which is outdated, to play the target one
+            if target == 0 {
+                existing_queue.playing_index = 0;
+                existing_queue.did_skip = true;
+            } else {
+                existing_queue.playing_index = target - 1;
             }
+            handle.queue().skip()?;
         }
     }
 
-    if let Interaction::Command(command) = interaction {
-        // Delete ephemeral response
-        command.delete_response(ctx).await?;
+    Ok(())
+}
+
+/// Moves the last `count` tracks of a queue to `at`, keeping their order. Songbird only appends,
+/// this places tracks enqueued out of order.
+pub(crate) fn move_last<T>(queue: &mut VecDeque<T>, count: usize, at: usize) {
+    let moved = queue.split_off(queue.len().saturating_sub(count));
+    let at = at.min(queue.len());
+    for (offset, track) in moved.into_iter().enumerate() {
+        queue.insert(at + offset, track);
     }
+}
 
-    Ok(())
+/// Source used to recreate a track Songbird already dropped.
+pub(crate) fn ytdl_source(
+    http_client: HttpClient,
+    url: String,
+    settings: &Settings,
+) -> YoutubeDl<'static> {
+    YoutubeDl::new(http_client, url).user_args(vec![
+        "-j".into(),
+        "-4".into(),
+        "-q".into(),
+        "--no-simulate".into(),
+        "-f".into(),
+        settings.ytdl_format_arg(),
+        "-R".into(),
+        "infinite".into(),
+        "--ignore-config".into(),
+        "--no-warnings".into(),
+        //        "--extractor-args".into(),
+        //        "youtube:player-client=tv".into(),
+        "--cache-dir".into(),
+        "./yt-dlp-cache".into(),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_last_tracks_keeping_their_order() {
+        let mut queue = VecDeque::from([0, 1, 2, 3, 4]);
+        move_last(&mut queue, 2, 1);
+        assert_eq!(queue, [0, 3, 4, 1, 2]);
+
+        let mut queue = VecDeque::from([0, 1, 2]);
+        move_last(&mut queue, 1, 10);
+        assert_eq!(queue, [0, 1, 2]);
+    }
 }
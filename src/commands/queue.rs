@@ -0,0 +1,100 @@
+use crate::QueueKey;
+use crate::errors::errors::BeatError;
+use crate::messages::messages::to_queue_page;
+use serde_json::json;
+use serenity::all::Interaction;
+use serenity::builder::CreateCommand;
+use serenity::client::Context;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PAGE_SIZE: usize = 10;
+/// Pages left untouched for longer than this stop responding to their buttons.
+const PAGE_TIMEOUT_SECS: u64 = 300;
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("queue").description("Shows the whole queue, page by page")
+}
+
+pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    if let Interaction::Command(command) = interaction {
+        let guild_id = command.guild_id.ok_or(BeatError::NoGuild)?;
+
+        let page = {
+            let queue_lock = {
+                let guard = ctx.data.read().await;
+                guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
+            };
+            let maybe_queue = queue_lock.read().await;
+            let queue = maybe_queue.get(&guild_id).ok_or(BeatError::NoQueue)?;
+
+            // Open on the page of the current track
+            let mut page = to_queue_page(queue, queue.playing_index / PAGE_SIZE, PAGE_SIZE, now);
+            page["flags"] = json!(64);
+            page
+        };
+
+        ctx.http
+            .create_interaction_response(
+                command.id,
+                &command.token,
+                &json!({"type": 4, "data": page}),
+                vec![],
+            )
+            .await?;
+    } else if let Interaction::Component(component) = interaction {
+        let guild_id = component.guild_id.ok_or(BeatError::NoGuild)?;
+
+        // queue:<action>:<page>:<issued>
+        let parts: Vec<&str> = component.data.custom_id.split(':').collect();
+        let (action, page, issued) = match parts.as_slice() {
+            ["queue", action, page, issued] => (
+                *action,
+                page.parse::<usize>().unwrap_or(0),
+                issued.parse::<u64>().unwrap_or(0),
+            ),
+            _ => return Err(BeatError::NoValidCommand),
+        };
+
+        let data = if now.saturating_sub(issued) > PAGE_TIMEOUT_SECS {
+            json!({
+                "content": "_This queue view expired, use `/queue` again._",
+                "embeds": [],
+                "components": []
+            })
+        } else {
+            let queue_lock = {
+                let guard = ctx.data.read().await;
+                guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
+            };
+            let maybe_queue = queue_lock.read().await;
+            let queue = maybe_queue.get(&guild_id).ok_or(BeatError::NoQueue)?;
+            let last = queue.queue.len().saturating_sub(1) / PAGE_SIZE;
+
+            let target = match action {
+                "first" => 0,
+                "prev" => page.saturating_sub(1),
+                "next" => page + 1,
+                "last" => last,
+                _ => page,
+            };
+
+            to_queue_page(queue, target, PAGE_SIZE, now)
+        };
+
+        ctx.http
+            .create_interaction_response(
+                component.id,
+                &component.token,
+                &json!({"type": 7, "data": data}),
+                vec![],
+            )
+            .await?;
+    }
+
+    Ok(())
+}
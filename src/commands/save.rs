@@ -6,6 +6,7 @@ use serenity::all::{
 };
 use std::fs::{create_dir_all, File};
 use std::io::Write;
+use tracing::debug;
 
 pub fn register() -> CreateCommand {
     CreateCommand::new("save")
@@ -31,38 +32,37 @@ pub async fn run(
         value: ResolvedValue::String(name),
         ..
     }) = options.first()
-    {
-        if let Some(guild_id) = if let Interaction::Command(command) = interaction {
+        && let Some(guild_id) = if let Interaction::Command(command) = interaction {
             command.defer_ephemeral(ctx).await?;
             Some(command.guild_id.ok_or(BeatError::NoGuild)?)
         } else {
             None
-        } {
-            let queue_lock = {
-                let guard = ctx.data.read().await;
-                guard.get::<QueueKey>().unwrap().clone()
-            };
+        }
+    {
+        let queue_lock = {
+            let guard = ctx.data.read().await;
+            guard.get::<QueueKey>().unwrap().clone()
+        };
 
-            let mut maybe_queue = queue_lock.write().await;
+        let mut maybe_queue = queue_lock.write().await;
 
-            if let Some(existing_queue) = maybe_queue.get_mut(&guild_id) {
-                println!("Queue exists: {:?}", existing_queue);
+        if let Some(existing_queue) = maybe_queue.get_mut(&guild_id) {
+            debug!(tracks = existing_queue.queue.len(), %name, "Saving playlist");
 
-                let urls: Vec<String> = existing_queue
-                    .queue
-                    .iter()
-                    .map(|track| track.source_url.clone().unwrap())
-                    .collect();
+            let urls: Vec<String> = existing_queue
+                .queue
+                .iter()
+                .filter_map(|track| track.metadata.source_url.clone())
+                .collect();
 
-                let urls = urls.join("\n");
+            let urls = urls.join("\n");
 
-                let dir_name = format!("./{}", guild_id);
-                let file_name = format!("{}/{}.playlist", dir_name, name);
+            let dir_name = format!("./{}", guild_id);
+            let file_name = format!("{}/{}.playlist", dir_name, name);
 
-                create_dir_all(dir_name)?;
+            create_dir_all(dir_name)?;
 
-                File::create(file_name)?.write_all(urls.as_bytes())?;
-            }
+            File::create(file_name)?.write_all(urls.as_bytes())?;
         }
     }
 
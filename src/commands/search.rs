@@ -0,0 +1,237 @@
+use crate::commands::play::play;
+use crate::errors::errors::BeatError;
+use crate::messages::messages::to_search_results;
+use crate::telemetry::metrics;
+use serde_json::json;
+use serenity::all::{ComponentInteractionDataKind, Interaction};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::client::Context;
+use serenity::json::Value;
+use serenity::model::application::{CommandOptionType, ResolvedOption, ResolvedValue};
+use songbird::input::AuxMetadata;
+use std::process::Stdio;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+use tracing::{debug, info_span};
+use tracing_futures::Instrument;
+
+const DEFAULT_RESULTS: usize = 5;
+const MAX_RESULTS: usize = 10;
+/// Searches left unpicked for longer than this are closed.
+const SEARCH_TIMEOUT_SECS: u64 = 120;
+const EXPIRED: &str = "_This search expired, use `/search` again._";
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("search")
+        .description("Searches YouTube and lets you pick the track to queue")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::String, "query", "What to search for")
+                .required(true)
+                .max_length(200),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::Integer, "results", "How many to show")
+                .min_int_value(1)
+                .max_int_value(MAX_RESULTS as u64),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &Interaction,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BeatError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    if let Interaction::Command(command) = interaction {
+        command.defer_ephemeral(ctx).await?;
+
+        let query = options
+            .iter()
+            .find_map(|option| match option.value {
+                ResolvedValue::String(query) if option.name == "query" => Some(query),
+                _ => None,
+            })
+            .ok_or(BeatError::NoValidCommand)?;
+        let count = options
+            .iter()
+            .find_map(|option| match option.value {
+                ResolvedValue::Integer(count) if option.name == "results" => Some(count as usize),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_RESULTS)
+            .clamp(1, MAX_RESULTS);
+
+        let results = ytdl_search(query, count)
+            .await
+            .ok_or(BeatError::Other("Searching failed"))?;
+
+        ctx.http
+            .edit_original_interaction_response(
+                &command.token,
+                &to_search_results(query, &results, now),
+                vec![],
+            )
+            .await?;
+
+        // Close the picker once it expired, unless a track was picked meanwhile
+        let http = ctx.http.clone();
+        let token = command.token.clone();
+        tokio::spawn(
+            async move {
+                tokio::time::sleep(Duration::from_secs(SEARCH_TIMEOUT_SECS)).await;
+
+                let unpicked = http
+                    .get_original_interaction_response(&token)
+                    .await
+                    .is_ok_and(|message| !message.components.is_empty());
+                if unpicked {
+                    debug!("Search expired");
+                    let _ = http
+                        .edit_original_interaction_response(
+                            &token,
+                            &json!({"content": EXPIRED, "components": []}),
+                            vec![],
+                        )
+                        .await;
+                }
+            }
+            .instrument(info_span!("search_timeout")),
+        );
+    } else if let Interaction::Component(component) = interaction {
+        // search:<issued>
+        let issued = match component.data.custom_id.split(':').collect::<Vec<_>>()[..] {
+            ["search", issued] => issued.parse::<u64>().unwrap_or(0),
+            _ => return Err(BeatError::NoValidCommand),
+        };
+        let url = match &component.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => values.first(),
+            _ => None,
+        }
+        .ok_or(BeatError::NoValidCommand)?;
+
+        if now.saturating_sub(issued) > SEARCH_TIMEOUT_SECS {
+            ctx.http
+                .create_interaction_response(
+                    component.id,
+                    &component.token,
+                    &json!({"type": 7, "data": {"content": EXPIRED, "components": []}}),
+                    vec![],
+                )
+                .await?;
+            return Ok(());
+        }
+
+        // Drop the menu right away so the track can't be picked twice
+        ctx.http
+            .create_interaction_response(
+                component.id,
+                &component.token,
+                &json!({"type": 7, "data": {"content": "_Adding the track..._", "components": []}}),
+                vec![],
+            )
+            .await?;
+
+        let result = play(ctx, interaction, Some(url), None).await;
+        let content = match &result {
+            Ok(()) => String::from("Added to the queue."),
+            Err(error) => format!("_{}_", error),
+        };
+        ctx.http
+            .edit_original_interaction_response(
+                &component.token,
+                &json!({ "content": content }),
+                vec![],
+            )
+            .await?;
+
+        result?;
+    }
+
+    Ok(())
+}
+
+/// Top `count` results of a YouTube search, without resolving the streams.
+pub(crate) async fn ytdl_search(query: &str, count: usize) -> Option<Vec<AuxMetadata>> {
+    let search = format!("ytsearch{}:{}", count, query);
+    let args = vec![search.as_str(), "-4", "--flat-playlist", "-j"];
+
+    let started = Instant::now();
+    let output = Command::new("yt-dlp")
+        .args(args)
+        .stdout(Stdio::piped())
+        // Suggestions give up on slow searches
+        .kill_on_drop(true)
+        .output()
+        .await
+        .inspect_err(|_| metrics::resolve_failed("search"))
+        .ok()?;
+    metrics::resolved("search", started.elapsed());
+
+    Some(parse_results(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Reads the JSON lines yt-dlp prints for a flat search, skipping the ones without a link.
+fn parse_results(output: &str) -> Vec<AuxMetadata> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter_map(|entry| {
+            let text = |key: &str| entry.get(key).and_then(Value::as_str).map(String::from);
+
+            Some(AuxMetadata {
+                source_url: Some(text("webpage_url").or_else(|| text("url"))?),
+                title: text("title"),
+                artist: text("channel").or_else(|| text("uploader")),
+                channel: text("channel"),
+                duration: entry
+                    .get("duration")
+                    .and_then(Value::as_f64)
+                    .map(Duration::from_secs_f64),
+                thumbnail: entry
+                    .get("thumbnails")
+                    .and_then(Value::as_array)
+                    .and_then(|thumbnails| thumbnails.last())
+                    .and_then(|thumbnail| thumbnail.get("url"))
+                    .and_then(Value::as_str)
+                    .map(String::from),
+                ..AuxMetadata::default()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_search_results() {
+        let output = concat!(
+            r#"{"url": "https://www.youtube.com/watch?v=dQw4w9WgXcQ", "title": "Never Gonna Give You Up", "channel": "Rick Astley", "duration": 212.0}"#,
+            "\n",
+            r#"{"title": "No link"}"#,
+            "\n",
+            "not json\n",
+            r#"{"webpage_url": "https://www.youtube.com/watch?v=live", "url": "ignored", "title": "Live", "uploader": "Someone", "duration": null}"#,
+        );
+
+        let results = parse_results(output);
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[0].source_url.as_deref(),
+            Some("https://www.youtube.com/watch?v=dQw4w9WgXcQ")
+        );
+        assert_eq!(results[0].artist.as_deref(), Some("Rick Astley"));
+        assert_eq!(results[0].duration, Some(Duration::from_secs(212)));
+        assert_eq!(
+            results[1].source_url.as_deref(),
+            Some("https://www.youtube.com/watch?v=live")
+        );
+        assert_eq!(results[1].artist.as_deref(), Some("Someone"));
+        assert_eq!(results[1].duration, None);
+    }
+}
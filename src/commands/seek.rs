@@ -0,0 +1,321 @@
+use crate::QueueKey;
+use crate::commands::play::songbird_manager;
+use crate::errors::errors::BeatError;
+use crate::http::api::publish;
+use crate::messages::messages::update_message;
+use crate::settings::settings::settings;
+use serenity::all::{GuildId, Interaction};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::client::Context;
+use serenity::http::Http;
+use serenity::model::application::{CommandOptionType, ResolvedOption, ResolvedValue};
+use serenity::prelude::TypeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use url::Url;
+
+/// Offset of the ±10s buttons, and of `/forward` and `/rewind` without `seconds`.
+const STEP: i64 = 10;
+
+/// Where to move in the current track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Seek {
+    To(Duration),
+    Forward(Duration),
+    Rewind(Duration),
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("seek")
+        .description("Moves to a position in the current track")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "timestamp",
+                "Position such as 1:23, 1:02:03, 83 or 1m23s",
+            )
+            .required(true)
+            .max_length(20),
+        )
+}
+
+pub fn register_forward() -> CreateCommand {
+    CreateCommand::new("forward")
+        .description("Skips ahead in the current track")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "seconds",
+                "Seconds to skip, 10 by default",
+            )
+            .min_int_value(1),
+        )
+}
+
+pub fn register_rewind() -> CreateCommand {
+    CreateCommand::new("rewind")
+        .description("Goes back in the current track")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "seconds",
+                "Seconds to go back, 10 by default",
+            )
+            .min_int_value(1),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &Interaction,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BeatError> {
+    if let Some((guild_id, position)) = if let Interaction::Command(command) = interaction {
+        command.defer_ephemeral(ctx).await?;
+        let position = match options.first() {
+            Some(ResolvedOption {
+                value: ResolvedValue::String(timestamp),
+                ..
+            }) => parse_timestamp(timestamp)
+                .ok_or(BeatError::Other("Not a valid timestamp, try 1:23 or 1m23s"))?,
+            _ => return Err(BeatError::NoValidCommand),
+        };
+        Some((command.guild_id.ok_or(BeatError::NoGuild)?, position))
+    } else {
+        None
+    } {
+        seek(&ctx.data, &ctx.http, guild_id, Seek::To(position)).await?;
+    }
+
+    if let Interaction::Command(command) = interaction {
+        // Delete ephemeral response
+        command.delete_response(ctx).await?;
+    }
+
+    Ok(())
+}
+
+/// Runs `/forward` and `/rewind` and their buttons, `direction` being 1 or -1 respectively.
+pub async fn run_relative(
+    ctx: &Context,
+    interaction: &Interaction,
+    options: &[ResolvedOption<'_>],
+    direction: i64,
+) -> Result<(), BeatError> {
+    if let Some((guild_id, seconds)) = if let Interaction::Command(command) = interaction {
+        command.defer_ephemeral(ctx).await?;
+        let seconds = match options.first() {
+            Some(ResolvedOption {
+                value: ResolvedValue::Integer(seconds),
+                ..
+            }) => (*seconds).max(1),
+            _ => STEP,
+        };
+        Some((command.guild_id.ok_or(BeatError::NoGuild)?, seconds))
+    } else if let Interaction::Component(component) = interaction {
+        component.defer_ephemeral(ctx).await?;
+        component.delete_response(ctx).await?;
+        Some((component.guild_id.ok_or(BeatError::NoGuild)?, STEP))
+    } else {
+        None
+    } {
+        relative(ctx, guild_id, direction * seconds).await?;
+    }
+
+    if let Interaction::Command(command) = interaction {
+        // Delete ephemeral response
+        command.delete_response(ctx).await?;
+    }
+
+    Ok(())
+}
+
+/// Moves `offset` seconds from the current position, back when negative.
+pub async fn relative(ctx: &Context, guild_id: GuildId, offset: i64) -> Result<(), BeatError> {
+    let by = Duration::from_secs(offset.unsigned_abs());
+    let seek_by = if offset < 0 {
+        Seek::Rewind(by)
+    } else {
+        Seek::Forward(by)
+    };
+
+    seek(&ctx.data, &ctx.http, guild_id, seek_by).await
+}
+
+/// Moves within the current track, clamped to its bounds, and refreshes the progress bar.
+/// Livestreams can't be seeked.
+pub async fn seek(
+    data: &Arc<RwLock<TypeMap>>,
+    http: &Http,
+    guild_id: GuildId,
+    seek: Seek,
+) -> Result<(), BeatError> {
+    let settings = settings(data, guild_id).await;
+    let queue_lock = {
+        let guard = data.read().await;
+        guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
+    };
+
+    let duration = {
+        let maybe_queue = queue_lock.read().await;
+        let queue = maybe_queue.get(&guild_id).ok_or(BeatError::NoQueue)?;
+        queue
+            .queue
+            .get(queue.playing_index)
+            .ok_or(BeatError::NoCurrentTrack)?
+            .metadata
+            .duration
+            .ok_or(BeatError::NotSeekable)?
+    };
+
+    let manager = songbird_manager(data).await?;
+    let handler_lock = manager.get(guild_id).ok_or(BeatError::NoManager)?;
+    let current = handler_lock
+        .lock()
+        .await
+        .queue()
+        .current()
+        .ok_or(BeatError::NoCurrentTrack)?;
+
+    let position = current.get_info().await?.position;
+    let target = match seek {
+        Seek::To(target) => target,
+        Seek::Forward(offset) => position + offset,
+        Seek::Rewind(offset) => position.saturating_sub(offset),
+    };
+
+    // Seeking to the very end would end the track, skipping is there for that
+    let target = target.min(duration.saturating_sub(Duration::from_secs(1)));
+
+    // Not under the queue lock, seeking may have to download up to the target
+    let reached = current.seek_async(target).await?;
+
+    let mut maybe_queue = queue_lock.write().await;
+
+    if let Some(queue) = maybe_queue.get_mut(&guild_id) {
+        queue.position = reached;
+
+        update_message(http, queue, &settings).await?;
+        publish(data, guild_id, queue).await;
+    }
+
+    Ok(())
+}
+
+/// Parses a position given as seconds (`83`), clock time (`1:23`, `1:02:03`) or with units
+/// (`1m23s`, `1h2m3s`). Positions too large to represent are rejected.
+pub fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let timestamp = timestamp.trim();
+
+    if timestamp.is_empty() {
+        return None;
+    }
+
+    let seconds = if timestamp.contains(':') {
+        let parts = timestamp
+            .split(':')
+            .map(|part| part.parse::<u64>().ok())
+            .collect::<Option<Vec<u64>>>()?;
+
+        if parts.len() > 3 {
+            return None;
+        }
+        parts.iter().try_fold(0u64, |total, part| {
+            total.checked_mul(60)?.checked_add(*part)
+        })?
+    } else if let Ok(seconds) = timestamp.parse::<u64>() {
+        seconds
+    } else {
+        let mut total = 0;
+        let mut number = String::new();
+
+        for char in timestamp.chars() {
+            if char.is_ascii_digit() {
+                number.push(char);
+                continue;
+            }
+
+            let unit = match char.to_ascii_lowercase() {
+                'h' => 3600,
+                'm' => 60,
+                's' => 1,
+                _ => return None,
+            };
+            total = number
+                .parse::<u64>()
+                .ok()?
+                .checked_mul(unit)
+                .and_then(|seconds| seconds.checked_add(total))?;
+            number.clear();
+        }
+
+        if !number.is_empty() {
+            return None;
+        }
+        total
+    };
+
+    Some(Duration::from_secs(seconds))
+}
+
+/// Start position carried by a link, from its `t` or `start` query parameter.
+pub fn start_position(url: &str) -> Option<Duration> {
+    Url::parse(url)
+        .ok()?
+        .query_pairs()
+        .find(|(key, _)| key == "t" || key == "start")
+        .and_then(|(_, value)| parse_timestamp(&value))
+        .filter(|start| !start.is_zero())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("83"), Some(Duration::from_secs(83)));
+        assert_eq!(parse_timestamp("1:23"), Some(Duration::from_secs(83)));
+        assert_eq!(parse_timestamp("1:02:03"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_timestamp("1m23s"), Some(Duration::from_secs(83)));
+        assert_eq!(parse_timestamp("1h2m3s"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_timestamp("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("1:2:3:4"), None);
+        assert_eq!(parse_timestamp("1m23"), None);
+        assert_eq!(parse_timestamp("soon"), None);
+    }
+
+    #[test]
+    fn rejects_overflowing_timestamps() {
+        assert_eq!(parse_timestamp("9999999999999999999h"), None);
+        assert_eq!(parse_timestamp("9999999999999999:0:0"), None);
+        assert_eq!(parse_timestamp("18446744073709551615s1s"), None);
+        assert_eq!(
+            start_position("https://youtu.be/dQw4w9WgXcQ?t=9999999999999999999m"),
+            None
+        );
+    }
+
+    #[test]
+    fn reads_start_from_links() {
+        assert_eq!(
+            start_position("https://youtu.be/dQw4w9WgXcQ?t=43"),
+            Some(Duration::from_secs(43))
+        );
+        assert_eq!(
+            start_position("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=1m2s"),
+            Some(Duration::from_secs(62))
+        );
+        assert_eq!(
+            start_position("https://www.youtube.com/embed/dQw4w9WgXcQ?start=30"),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            start_position("https://www.youtube.com/watch?v=dQw4w9WgXcQ"),
+            None
+        );
+        assert_eq!(start_position("never gonna give you up"), None);
+    }
+}
@@ -0,0 +1,459 @@
+use crate::commands::volume;
+use crate::errors::errors::BeatError;
+use crate::settings::settings::{
+    Language, MAX_VOLUME, MIN_VOLUME, Settings, Transition, Verbosity, settings, update,
+};
+use serde_json::json;
+use serenity::all::{
+    ChannelType, CommandOptionType, Context, CreateCommand, CreateCommandOption, Interaction,
+    Permissions, ResolvedOption, ResolvedValue,
+};
+use serenity::json::Value;
+
+pub fn register() -> CreateCommand {
+    let subcommand = |name: &str, description: &str| {
+        CreateCommandOption::new(CommandOptionType::SubCommand, name, description)
+    };
+    let integer = |name: &str, description: &str, min: u64, max: u64| {
+        CreateCommandOption::new(CommandOptionType::Integer, name, description)
+            .min_int_value(min)
+            .max_int_value(max)
+    };
+
+    CreateCommand::new("settings")
+        .description("Shows or changes the settings of Beat for this server")
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .add_option(subcommand("show", "Shows the current settings"))
+        .add_option(subcommand("reset", "Restores the default settings"))
+        .add_option(
+            subcommand("volume", "Volume of new tracks").add_sub_option(
+                integer(
+                    "percent",
+                    "Volume in percent",
+                    MIN_VOLUME as u64,
+                    MAX_VOLUME as u64,
+                )
+                .required(true),
+            ),
+        )
+        .add_option(
+            subcommand("channel", "Binds Beat to a text channel, or unbinds it").add_sub_option(
+                CreateCommandOption::new(CommandOptionType::Channel, "channel", "The channel")
+                    .channel_types(vec![ChannelType::Text]),
+            ),
+        )
+        .add_option(
+            subcommand("dj-role", "Role allowed to skip, go back, jump and stop").add_sub_option(
+                CreateCommandOption::new(CommandOptionType::Role, "role", "The role"),
+            ),
+        )
+        .add_option(
+            subcommand("vote-skip", "Share of the listeners needed to skip without a DJ")
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Number,
+                        "ratio",
+                        "Between 0.1 and 1, 0.5 by default",
+                    )
+                    .min_number_value(0.1)
+                    .max_number_value(1.0)
+                    .required(true),
+                ),
+        )
+        .add_option(
+            subcommand(
+                "max-queue",
+                "Maximum number of tracks in the queue, or unlimited",
+            )
+            .add_sub_option(integer("length", "Number of tracks", 1, 10_000)),
+        )
+        .add_option(
+            subcommand("max-duration", "Maximum duration of a track, or unlimited")
+                .add_sub_option(integer("minutes", "Duration in minutes", 1, 24 * 60)),
+        )
+        .add_option(
+            subcommand("idle-timeout", "Time to stay connected once the queue ends")
+                .add_sub_option(
+                    integer("seconds", "0 to leave right away", 0, 3600).required(true),
+                ),
+        )
+        .add_option(
+            subcommand("verbosity", "How much the now-playing message shows").add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "level", "The level")
+                    .add_string_choice("Compact", "compact")
+                    .add_string_choice("Normal", "normal")
+                    .add_string_choice("Detailed", "detailed")
+                    .required(true),
+            ),
+        )
+        .add_option(
+            subcommand("language", "Language of the now-playing message").add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "language", "The language")
+                    .add_string_choice("English", "english")
+                    .add_string_choice("Français", "french")
+                    .required(true),
+            ),
+        )
+        .add_option(
+            subcommand(
+                "preload",
+                "Seconds into a track, or before its end unless cutting, to load the next one",
+            )
+            .add_sub_option(integer("seconds", "Seconds", 1, 60).required(true)),
+        )
+        .add_option(
+            subcommand("prev-preload", "Same, for tracks recreated when going back")
+                .add_sub_option(integer("seconds", "Seconds", 1, 60).required(true)),
+        )
+        .add_option(
+            subcommand("preview", "Tracks listed around the current one")
+                .add_sub_option(integer("tracks", "Tracks before and after", 0, 10).required(true)),
+        )
+        .add_option(
+            subcommand("normalize", "Evens out the loudness of tracks")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Boolean, "enabled", "Enabled")
+                        .required(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Number,
+                        "target",
+                        "Target loudness in LUFS, -14 by default",
+                    )
+                    .min_number_value(-30.0)
+                    .max_number_value(-5.0),
+                ),
+        )
+        .add_option(
+            subcommand("transition", "How one track leads into the next")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::String, "mode", "The mode")
+                        .add_string_choice("Cut", "cut")
+                        .add_string_choice("Gapless", "gapless")
+                        .add_string_choice("Crossfade", "crossfade")
+                        .required(true),
+                )
+                .add_sub_option(integer(
+                    "overlap",
+                    "Crossfade in seconds, 5 by default",
+                    1,
+                    15,
+                )),
+        )
+        .add_option(
+            subcommand(
+                "suggestions",
+                "Suggests YouTube results while typing in /play",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::Boolean, "enabled", "Enabled")
+                    .required(true),
+            ),
+        )
+        .add_option(
+            subcommand("autoplay", "Plays related tracks once the queue runs out")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Boolean, "enabled", "Enabled")
+                        .required(true),
+                )
+                .add_sub_option(integer(
+                    "tracks",
+                    "Tracks played without a listener doing anything, 10 by default",
+                    1,
+                    100,
+                )),
+        )
+        .add_option(
+            subcommand("format", "yt-dlp format selector").add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "format", "The format")
+                    .max_length(200)
+                    .required(true),
+            ),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &Interaction,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BeatError> {
+    if let Interaction::Command(command) = interaction {
+        let guild_id = command.guild_id.ok_or(BeatError::NoGuild)?;
+
+        let Some(ResolvedOption {
+            name,
+            value: ResolvedValue::SubCommand(arguments),
+            ..
+        }) = options.first()
+        else {
+            return Err(BeatError::NoValidCommand);
+        };
+
+        let integer = arguments.iter().find_map(|option| match option.value {
+            ResolvedValue::Integer(value) => Some(value.max(0) as u64),
+            _ => None,
+        });
+        let string = arguments.iter().find_map(|option| match option.value {
+            ResolvedValue::String(value) => Some(String::from(value)),
+            _ => None,
+        });
+
+        let settings = match *name {
+            "show" => settings(&ctx.data, guild_id).await,
+            "reset" => {
+                update(&ctx.data, guild_id, |settings| {
+                    *settings = Settings::default()
+                })
+                .await?
+            }
+            "volume" => {
+                let percent = integer.unwrap_or(100).min(MAX_VOLUME as u64) as u8;
+                volume::set(&ctx.data, &ctx.http, guild_id, percent).await?
+            }
+            "channel" => {
+                let channel = arguments.iter().find_map(|option| match &option.value {
+                    ResolvedValue::Channel(channel) => Some(channel.id),
+                    _ => None,
+                });
+                update(&ctx.data, guild_id, |settings| {
+                    settings.music_channel = channel
+                })
+                .await?
+            }
+            "dj-role" => {
+                let role = arguments.iter().find_map(|option| match option.value {
+                    ResolvedValue::Role(role) => Some(role.id),
+                    _ => None,
+                });
+                update(&ctx.data, guild_id, |settings| settings.dj_role = role).await?
+            }
+            "vote-skip" => {
+                let ratio = arguments.iter().find_map(|option| match option.value {
+                    ResolvedValue::Number(ratio) => Some(ratio.clamp(0.1, 1.0)),
+                    _ => None,
+                });
+                update(&ctx.data, guild_id, |settings| {
+                    settings.vote_skip_ratio = ratio.unwrap_or(0.5)
+                })
+                .await?
+            }
+            "max-queue" => {
+                update(&ctx.data, guild_id, |settings| {
+                    settings.max_queue_length = integer.map(|length| length as usize)
+                })
+                .await?
+            }
+            "max-duration" => {
+                update(&ctx.data, guild_id, |settings| {
+                    settings.max_track_duration_secs = integer.map(|minutes| minutes * 60)
+                })
+                .await?
+            }
+            "idle-timeout" => {
+                update(&ctx.data, guild_id, |settings| {
+                    settings.idle_timeout_secs = integer.unwrap_or_default()
+                })
+                .await?
+            }
+            "verbosity" => {
+                let verbosity = match string.as_deref() {
+                    Some("compact") => Verbosity::Compact,
+                    Some("detailed") => Verbosity::Detailed,
+                    _ => Verbosity::Normal,
+                };
+                update(&ctx.data, guild_id, |settings| {
+                    settings.verbosity = verbosity
+                })
+                .await?
+            }
+            "language" => {
+                let language = match string.as_deref() {
+                    Some("french") => Language::French,
+                    _ => Language::English,
+                };
+                update(&ctx.data, guild_id, |settings| settings.language = language).await?
+            }
+            "preload" => {
+                update(&ctx.data, guild_id, |settings| {
+                    settings.preload_secs = integer.unwrap_or(10)
+                })
+                .await?
+            }
+            "prev-preload" => {
+                update(&ctx.data, guild_id, |settings| {
+                    settings.rebuild_preload_secs = integer.unwrap_or(15)
+                })
+                .await?
+            }
+            "preview" => {
+                update(&ctx.data, guild_id, |settings| {
+                    settings.preview_tracks = integer.unwrap_or(2) as usize
+                })
+                .await?
+            }
+            "normalize" => {
+                let enabled = arguments.iter().any(|option| {
+                    option.name == "enabled" && matches!(option.value, ResolvedValue::Boolean(true))
+                });
+                let target = arguments.iter().find_map(|option| match option.value {
+                    ResolvedValue::Number(target) => Some(target as f32),
+                    _ => None,
+                });
+                update(&ctx.data, guild_id, |settings| {
+                    settings.normalize = enabled;
+                    if let Some(target) = target {
+                        settings.target_lufs = target;
+                    }
+                })
+                .await?
+            }
+            "transition" => {
+                let transition = match string.as_deref() {
+                    Some("gapless") => Transition::Gapless,
+                    Some("crossfade") => Transition::Crossfade,
+                    _ => Transition::Cut,
+                };
+                update(&ctx.data, guild_id, |settings| {
+                    settings.transition = transition;
+                    if let Some(overlap) = integer {
+                        settings.crossfade_secs = overlap;
+                    }
+                })
+                .await?
+            }
+            "suggestions" => {
+                let enabled = arguments
+                    .iter()
+                    .any(|option| matches!(option.value, ResolvedValue::Boolean(true)));
+                update(&ctx.data, guild_id, |settings| {
+                    settings.search_suggestions = enabled
+                })
+                .await?
+            }
+            "autoplay" => {
+                let enabled = arguments.iter().any(|option| {
+                    option.name == "enabled" && matches!(option.value, ResolvedValue::Boolean(true))
+                });
+                update(&ctx.data, guild_id, |settings| {
+                    settings.autoplay = enabled;
+                    if let Some(tracks) = integer {
+                        settings.autoplay_limit = tracks as usize;
+                    }
+                })
+                .await?
+            }
+            "format" => {
+                let Some(format) = string else {
+                    return Err(BeatError::NoValidCommand);
+                };
+                update(&ctx.data, guild_id, |settings| {
+                    settings.ytdl_format = format
+                })
+                .await?
+            }
+            _ => return Err(BeatError::NoValidCommand),
+        };
+
+        let response = json!({
+            "type": 4,
+            "data": {
+                "embeds": [to_settings_embed(&settings)],
+                "flags": 64
+            }
+        });
+
+        ctx.http
+            .create_interaction_response(command.id, &command.token, &response, vec![])
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn to_settings_embed(settings: &Settings) -> Value {
+    let or_unset = |value: Option<String>, unset: &str| value.unwrap_or(String::from(unset));
+
+    let lines = [
+        format!("**Default volume:** {}%", settings.default_volume),
+        format!(
+            "**Music channel:** {}",
+            or_unset(settings.music_channel.map(|id| format!("<#{}>", id)), "Any")
+        ),
+        format!(
+            "**DJ role:** {}",
+            or_unset(
+                settings.dj_role.map(|id| format!("<@&{}>", id)),
+                "Role named DJ"
+            )
+        ),
+        format!(
+            "**Vote-skip:** {}% of the listeners",
+            (settings.vote_skip_ratio * 100.0).round()
+        ),
+        format!(
+            "**Max queue length:** {}",
+            or_unset(
+                settings.max_queue_length.map(|length| length.to_string()),
+                "Unlimited"
+            )
+        ),
+        format!(
+            "**Max track duration:** {}",
+            or_unset(
+                settings
+                    .max_track_duration_secs
+                    .map(|secs| format!("{} min", secs / 60)),
+                "Unlimited"
+            )
+        ),
+        format!("**Idle timeout:** {}s", settings.idle_timeout_secs),
+        format!("**Verbosity:** {:?}", settings.verbosity),
+        format!("**Language:** {:?}", settings.language),
+        format!(
+            "**Preload:** {}s, {}s when going back",
+            settings.preload_secs, settings.rebuild_preload_secs
+        ),
+        format!(
+            "**Preview:** {} tracks around the current one",
+            settings.preview_tracks
+        ),
+        format!(
+            "**Transition:** {}",
+            match settings.transition {
+                Transition::Crossfade => format!("Crossfade over {}s", settings.crossfade_secs),
+                transition => format!("{:?}", transition),
+            }
+        ),
+        format!(
+            "**YouTube suggestions:** {}",
+            if settings.search_suggestions {
+                "On"
+            } else {
+                "Off"
+            }
+        ),
+        format!(
+            "**Autoplay:** {}",
+            if settings.autoplay {
+                format!("Up to {} tracks in a row", settings.autoplay_limit)
+            } else {
+                String::from("Off")
+            }
+        ),
+        format!("**yt-dlp format:** `{}`", settings.ytdl_format),
+        format!(
+            "**Loudness normalisation:** {}",
+            if settings.normalize {
+                format!("{} LUFS", settings.target_lufs)
+            } else {
+                String::from("Off")
+            }
+        ),
+    ];
+
+    json!({
+        "title": "**Settings**",
+        "description": lines.join("\n"),
+    })
+}
@@ -1,56 +1,32 @@
-use crate::errors::errors::BeatError;
 use crate::QueueKey;
-use serenity::all::{Interaction, MessageId};
+use crate::commands::play::songbird_manager;
+use crate::errors::errors::BeatError;
+use crate::http::api::publish;
+use crate::telemetry::metrics;
+use serenity::all::{GuildId, Interaction};
 use serenity::builder::CreateCommand;
 use serenity::client::Context;
+use serenity::http::Http;
+use serenity::prelude::TypeMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
 
 pub fn register() -> CreateCommand {
     CreateCommand::new("stop").description("Stops and disconnects Beat")
 }
 
 pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatError> {
-    if let Some((guild_id, channel_id)) = if let Interaction::Command(command) = interaction {
+    if let Some(guild_id) = if let Interaction::Command(command) = interaction {
         command.defer_ephemeral(ctx).await?;
-        Some((
-            command.guild_id.ok_or(BeatError::NoGuild)?,
-            command.channel_id,
-        ))
+        Some(command.guild_id.ok_or(BeatError::NoGuild)?)
     } else if let Interaction::Component(component) = interaction {
         component.defer_ephemeral(ctx).await?;
         component.delete_response(ctx).await?;
-        Some((
-            component.guild_id.ok_or(BeatError::NoGuild)?,
-            component.channel_id,
-        ))
+        Some(component.guild_id.ok_or(BeatError::NoGuild)?)
     } else {
         None
     } {
-        let queue_lock = {
-            let guard = ctx.data.write().await;
-            guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
-        };
-
-        let mut maybe_queue = queue_lock.write().await;
-
-        // Delete the queue message
-        if let Some(queue) = maybe_queue.get(&guild_id) {
-            if let Some(message_id) = queue.message_id {
-                ctx.http
-                    .delete_message(channel_id, MessageId::from(message_id), None)
-                    .await
-                    .unwrap_or_default();
-            }
-        }
-
-        // Delete Beat data for the guild
-        maybe_queue.get_mut(&guild_id).ok_or(BeatError::NoQueue)?.reset();
-
-        // Disconnect and clear Songbird for the guild
-        let manager = songbird::get(ctx)
-            .await
-            .ok_or(BeatError::NoSongbird)?
-            .clone();
-        manager.remove(guild_id).await?;
+        stop(&ctx.data, &ctx.http, guild_id).await?;
     }
 
     if let Interaction::Command(command) = interaction {
@@ -60,3 +36,39 @@ pub async fn run(ctx: &Context, interaction: &Interaction) -> Result<(), BeatErr
 
     Ok(())
 }
+
+pub async fn stop(
+    data: &Arc<RwLock<TypeMap>>,
+    http: &Http,
+    guild_id: GuildId,
+) -> Result<(), BeatError> {
+    let queue_lock = {
+        let guard = data.read().await;
+        guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
+    };
+
+    let mut maybe_queue = queue_lock.write().await;
+
+    // Delete the queue message
+    if let Some(queue) = maybe_queue.get(&guild_id)
+        && let (Some(channel_id), Some(message_id)) = (queue.channel_id, queue.message_id)
+    {
+        http.delete_message(channel_id, message_id, None)
+            .await
+            .unwrap_or_default();
+    }
+
+    // Delete Beat data for the guild
+    let queue = maybe_queue.get_mut(&guild_id).ok_or(BeatError::NoQueue)?;
+    queue.reset();
+    publish(data, guild_id, queue).await;
+
+    // Disconnect and clear Songbird for the guild
+    let manager = songbird_manager(data).await?;
+    manager.remove(guild_id).await?;
+
+    metrics::voice_disconnected();
+    metrics::queue_length(guild_id, 0);
+
+    Ok(())
+}
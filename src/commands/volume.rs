@@ -0,0 +1,105 @@
+use crate::QueueKey;
+use crate::audio::loudness;
+use crate::commands::play::songbird_manager;
+use crate::errors::errors::BeatError;
+use crate::http::api::publish;
+use crate::messages::messages::update_message;
+use crate::settings::settings::{MAX_VOLUME, MIN_VOLUME, Settings, settings, update};
+use serenity::all::{GuildId, Interaction};
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::client::Context;
+use serenity::http::Http;
+use serenity::model::application::{CommandOptionType, ResolvedOption, ResolvedValue};
+use serenity::prelude::TypeMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Change applied by the volume buttons, in percent.
+const STEP: u8 = 10;
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("volume")
+        .description("Sets the volume of the current and upcoming tracks")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::Integer, "percent", "Volume in percent")
+                .min_int_value(MIN_VOLUME as u64)
+                .max_int_value(MAX_VOLUME as u64)
+                .required(true),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &Interaction,
+    options: &[ResolvedOption<'_>],
+) -> Result<(), BeatError> {
+    if let Some((guild_id, percent)) = if let Interaction::Command(command) = interaction {
+        command.defer_ephemeral(ctx).await?;
+        let percent = match options.first() {
+            Some(ResolvedOption {
+                value: ResolvedValue::Integer(percent),
+                ..
+            }) => (*percent).clamp(MIN_VOLUME as i64, MAX_VOLUME as i64) as u8,
+            _ => return Err(BeatError::NoValidCommand),
+        };
+        Some((command.guild_id.ok_or(BeatError::NoGuild)?, percent))
+    } else if let Interaction::Component(component) = interaction {
+        component.defer_ephemeral(ctx).await?;
+        component.delete_response(ctx).await?;
+        let guild_id = component.guild_id.ok_or(BeatError::NoGuild)?;
+        let current = settings(&ctx.data, guild_id).await.default_volume;
+        // `volume:up` or `volume:down`
+        let percent = match component.data.custom_id.split(':').nth(1) {
+            Some("up") => current.saturating_add(STEP).min(MAX_VOLUME),
+            Some("down") => current.saturating_sub(STEP),
+            _ => return Err(BeatError::NoValidCommand),
+        };
+        Some((guild_id, percent))
+    } else {
+        None
+    } {
+        set(&ctx.data, &ctx.http, guild_id, percent).await?;
+    }
+
+    if let Interaction::Command(command) = interaction {
+        // Delete ephemeral response
+        command.delete_response(ctx).await?;
+    }
+
+    Ok(())
+}
+
+/// Sets the volume of the current track and of every track queued after it, and keeps it as the
+/// guild default for the tracks enqueued or recreated later.
+pub async fn set(
+    data: &Arc<RwLock<TypeMap>>,
+    http: &Http,
+    guild_id: GuildId,
+    percent: u8,
+) -> Result<Settings, BeatError> {
+    let settings = update(data, guild_id, |settings| settings.default_volume = percent).await?;
+
+    if let Ok(manager) = songbird_manager(data).await
+        && let Some(handler_lock) = manager.get(guild_id)
+    {
+        let tracks = handler_lock.lock().await.queue().current_queue();
+        for track in tracks {
+            // Keep the loudness normalisation of each track
+            track.set_volume(settings.volume() * loudness::gain(&track))?;
+        }
+    }
+
+    let queue_lock = {
+        let guard = data.read().await;
+        guard.get::<QueueKey>().ok_or(BeatError::NoQueues)?.clone()
+    };
+
+    let maybe_queue = queue_lock.read().await;
+
+    if let Some(queue) = maybe_queue.get(&guild_id) {
+        update_message(http, queue, &settings).await?;
+        publish(data, guild_id, queue).await;
+    }
+
+    Ok(settings)
+}
@@ -1,8 +1,10 @@
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
+use crate::telemetry::metrics;
 use serenity::prelude::SerenityError;
 use songbird::error::{ControlError, JoinError};
 use songbird::input::AudioStreamError;
+use tracing::error;
 use url::ParseError;
 
 #[derive(Debug)]
@@ -19,6 +21,11 @@ pub enum BeatError {
     NoCurrentTrack,
     NoCurrentSourceUrl,
     NoValidCommand,
+    NoSuchTrack,
+    Forbidden(&'static str),
+    QueueFull,
+    TrackTooLong,
+    NotSeekable,
     Stopping,
 }
 
@@ -37,6 +44,11 @@ impl Display for BeatError {
             Self::NoCurrentTrack => f.write_str("No current track to load"),
             Self::NoCurrentSourceUrl => f.write_str("Current track has no source URL"),
             Self::NoValidCommand => f.write_str("Not a valid command"),
+            Self::NoSuchTrack => f.write_str("No track at that position"),
+            Self::Forbidden(msg) => f.write_str(msg),
+            Self::QueueFull => f.write_str("The queue is full"),
+            Self::TrackTooLong => f.write_str("The track is longer than allowed"),
+            Self::NotSeekable => f.write_str("Livestreams can't be seeked"),
             Self::Stopping => f.write_str("Bot is stopping, should stop handling new songs"),
         }
     }
@@ -46,42 +58,43 @@ impl Error for BeatError {}
 
 impl From<std::io::Error> for BeatError {
     fn from(why: std::io::Error) -> Self {
-        eprintln!("{:?}", why);
+        error!(?why, "JSON error");
         Self::Other("JSON error")
     }
 }
 
 impl From<AudioStreamError> for BeatError {
     fn from(why: AudioStreamError) -> Self {
-        eprintln!("{:?}", why);
+        error!(?why, "Audio stream error");
         Self::Other("Audio stream error")
     }
 }
 
 impl From<SerenityError> for BeatError {
     fn from(why: SerenityError) -> Self {
-        eprintln!("{:?}", why);
+        error!(?why, "Serenity error");
+        metrics::discord_error();
         Self::Other("Serenity error")
     }
 }
 
 impl From<JoinError> for BeatError {
     fn from(why: JoinError) -> Self {
-        eprintln!("{:?}", why);
+        error!(?why, "Could not join channel");
         Self::Other("Could not join channel")
     }
 }
 
 impl From<ParseError> for BeatError {
     fn from(why: ParseError) -> Self {
-        eprintln!("{:?}", why);
+        error!(?why, "Could not parse URL");
         Self::Other("Could not parse URL")
     }
 }
 
 impl From<ControlError> for BeatError {
     fn from(why: ControlError) -> Self {
-        eprintln!("{:?}", why);
+        error!(?why, "Could not run control");
         Self::Other("Could not run control")
     }
 }
\ No newline at end of file
@@ -1 +1,2 @@
+#[allow(clippy::module_inception)]
 pub(crate) mod errors;
\ No newline at end of file
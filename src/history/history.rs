@@ -0,0 +1,197 @@
+use crate::QueuedTrack;
+use crate::errors::errors::BeatError;
+use serde::{Deserialize, Serialize};
+use serenity::all::{GuildId, UserId};
+use serenity::prelude::{TypeMap, TypeMapKey};
+use songbird::tracks::{TrackHandle, TrackState};
+use std::fs;
+use std::fs::{OpenOptions, create_dir_all};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, warn};
+
+/// Entries kept per guild, the oldest ones are dropped past it.
+pub const MAX_ENTRIES: usize = 1000;
+
+/// Tracks playing right now, recorded to the history once they end.
+pub struct HistoryKey;
+
+impl TypeMapKey for HistoryKey {
+    type Value = Arc<Mutex<Vec<Playing>>>;
+}
+
+pub struct Playing {
+    track: TrackHandle,
+    guild_id: GuildId,
+    entry: Entry,
+}
+
+/// A played track, persisted in `./<guild_id>/history.jsonl`, one entry per line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub url: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration_secs: Option<u64>,
+    /// `None` for tracks added through the HTTP API
+    pub requester: Option<UserId>,
+    /// Milliseconds since the Unix epoch, which also identifies the entry
+    pub started_at: u64,
+    pub listened_secs: u64,
+}
+
+fn file_name(guild_id: GuildId) -> String {
+    format!("./{}/history.jsonl", guild_id)
+}
+
+/// History of a guild, oldest first.
+pub fn entries(guild_id: GuildId) -> Vec<Entry> {
+    fs::read_to_string(file_name(guild_id))
+        .map(|content| parse(&content))
+        .unwrap_or_default()
+}
+
+fn parse(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            serde_json::from_str(line)
+                .map_err(|error| warn!(?error, "Invalid history entry, skipping it"))
+                .ok()
+        })
+        .collect()
+}
+
+fn store(guild_id: GuildId, entry: &Entry) -> Result<(), BeatError> {
+    create_dir_all(format!("./{}", guild_id))?;
+    let line = serde_json::to_string(entry)
+        .map_err(|_| BeatError::Other("Could not serialize history entry"))?;
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file_name(guild_id))?
+        .write_all(format!("{}\n", line).as_bytes())?;
+
+    // Trim by rewriting the file, appending stays cheap until then
+    let entries = entries(guild_id);
+    if entries.len() > MAX_ENTRIES {
+        let kept = entries[entries.len() - MAX_ENTRIES..]
+            .iter()
+            .filter_map(|entry| serde_json::to_string(entry).ok())
+            .map(|line| format!("{}\n", line))
+            .collect::<String>();
+        fs::write(file_name(guild_id), kept)?;
+    }
+
+    Ok(())
+}
+
+/// Remembers that a queued track started playing.
+pub async fn started(
+    data: &Arc<RwLock<TypeMap>>,
+    guild_id: GuildId,
+    track: &TrackHandle,
+    queued: &QueuedTrack,
+) {
+    // Only what can be queued again is worth remembering
+    let Some(url) = queued.metadata.source_url.clone() else {
+        return;
+    };
+    let Some(playing) = data.read().await.get::<HistoryKey>().cloned() else {
+        return;
+    };
+
+    let entry = Entry {
+        url,
+        title: queued.metadata.title.clone(),
+        artist: queued
+            .metadata
+            .artist
+            .clone()
+            .or(queued.metadata.channel.clone()),
+        duration_secs: queued.metadata.duration.map(|duration| duration.as_secs()),
+        requester: queued.requester,
+        started_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64,
+        listened_secs: 0,
+    };
+
+    let mut playing = playing.lock().await;
+    // Resuming a paused track starts it again, it still counts once
+    if playing
+        .iter()
+        .any(|playing| playing.track.uuid() == track.uuid())
+    {
+        return;
+    }
+    playing.push(Playing {
+        track: track.clone(),
+        guild_id,
+        entry,
+    });
+}
+
+/// Records the tracks that stopped playing with how long they were listened to.
+pub async fn ended(data: &Arc<RwLock<TypeMap>>, tracks: &[(&TrackState, &TrackHandle)]) {
+    let Some(playing) = data.read().await.get::<HistoryKey>().cloned() else {
+        return;
+    };
+
+    let ended: Vec<Playing> = {
+        let mut playing = playing.lock().await;
+        let (ended, still_playing) = playing.drain(..).partition(|playing| {
+            tracks
+                .iter()
+                .any(|(_, track)| track.uuid() == playing.track.uuid())
+        });
+        *playing = still_playing;
+        ended
+    };
+
+    for Playing {
+        track,
+        guild_id,
+        mut entry,
+    } in ended
+    {
+        entry.listened_secs = tracks
+            .iter()
+            .find(|(_, ended)| ended.uuid() == track.uuid())
+            .map(|(state, _)| state.play_time.as_secs())
+            .unwrap_or_default();
+
+        debug!(%guild_id, url = %entry.url, listened_secs = entry.listened_secs, "Recording history");
+        store(guild_id, &entry)
+            .map_err(|error| warn!(?error, %guild_id, "Failed to record history"))
+            .unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_invalid_lines() {
+        let content = concat!(
+            r#"{"url": "https://youtu.be/a", "title": "A", "artist": null, "duration_secs": 212, "requester": "42", "started_at": 1700000000000, "listened_secs": 90}"#,
+            "\n",
+            "{broken\n",
+            r#"{"url": "https://youtu.be/b", "title": null, "artist": "B", "duration_secs": null, "requester": null, "started_at": 1700000300000, "listened_secs": 3600}"#,
+            "\n",
+        );
+
+        let entries = parse(content);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].requester, Some(UserId::new(42)));
+        assert_eq!(entries[0].listened_secs, 90);
+        assert_eq!(entries[1].url, "https://youtu.be/b");
+        assert_eq!(entries[1].duration_secs, None);
+    }
+}
@@ -0,0 +1,307 @@
+use crate::commands::play::{append_to_session, songbird_manager};
+use crate::commands::{next, pause, prev, repeat, stop};
+use crate::errors::errors::BeatError;
+use crate::http::server::ServerState;
+use crate::messages::messages::{track_artist, track_title};
+use crate::{Queue, QueueKey, QueuedTrack, TrackSource};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+use serenity::all::GuildId;
+use serenity::json::Value;
+use serenity::prelude::{TypeMap, TypeMapKey};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use tokio::sync::RwLock;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, info_span, warn};
+use tracing_futures::Instrument;
+
+/// Queue state changes, serialized once and fanned out to every WebSocket client.
+pub struct FeedKey;
+
+impl TypeMapKey for FeedKey {
+    type Value = broadcast::Sender<(GuildId, String)>;
+}
+
+pub fn feed() -> broadcast::Sender<(GuildId, String)> {
+    broadcast::channel(64).0
+}
+
+/// Pushes the queue state of a guild to the WebSocket feed.
+pub async fn publish(data: &Arc<RwLock<TypeMap>>, guild_id: GuildId, queue: &Queue) {
+    let feed = {
+        let guard = data.read().await;
+        guard.get::<FeedKey>().cloned()
+    };
+
+    if let Some(feed) = feed {
+        let event = json!({"type": "queue", "queue": queue_state(guild_id, queue)});
+        // No subscriber is not an error
+        let _ = feed.send((guild_id, event.to_string()));
+    }
+}
+
+pub fn queue_state(guild_id: GuildId, queue: &Queue) -> Value {
+    json!({
+        "guild_id": guild_id.to_string(),
+        "playing_index": queue.playing_index,
+        "paused": queue.pause,
+        "repeat": queue.repeat,
+        "now_playing": queue.queue.get(queue.playing_index).map(track_json),
+        "tracks": queue.queue.iter().map(track_json).collect::<Vec<Value>>(),
+    })
+}
+
+fn track_json(entry: &QueuedTrack) -> Value {
+    let track = &entry.metadata;
+    let (source, source_name) = match &entry.source {
+        TrackSource::Search(query) => ("search", Some(query)),
+        TrackSource::Url => ("url", None),
+        TrackSource::Playlist(name) => ("playlist", Some(name)),
+        TrackSource::Autoplay => ("autoplay", None),
+        TrackSource::Library => ("library", None),
+        TrackSource::Podcast(episode) => ("podcast", episode.show.as_ref()),
+    };
+
+    json!({
+        "title": track_title(track),
+        "artist": track_artist(track),
+        "duration_secs": track.duration.map(|duration| duration.as_secs()),
+        "live": track.duration.is_none(),
+        "source_url": track.source_url,
+        "thumbnail": track.thumbnail,
+        "requester_id": entry.requester.map(|requester| requester.to_string()),
+        "queued_at": entry
+            .queued_at
+            .duration_since(UNIX_EPOCH)
+            .map(|queued_at| queued_at.as_secs())
+            .unwrap_or_default(),
+        "source": source,
+        "source_name": source_name,
+    })
+}
+
+/// Routes of the control API, all behind the `BEAT_API_TOKEN` bearer token.
+pub fn router(state: ServerState) -> Router<ServerState> {
+    Router::new()
+        .route("/guilds", get(guilds))
+        .route("/guilds/{guild_id}/queue", get(queue))
+        .route("/guilds/{guild_id}/play", post(play))
+        .route("/guilds/{guild_id}/{action}", post(action))
+        .route("/ws", get(websocket))
+        .layer(middleware::from_fn_with_state(state, authenticate))
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+async fn authenticate(
+    State(state): State<ServerState>,
+    Query(query): Query<TokenQuery>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(expected) = state.api_token.as_deref() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    // Browsers can not set headers on WebSocket upgrades, hence the query fallback
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(String::from)
+        .or(query.token);
+
+    if provided.is_some_and(|provided| constant_time_eq(provided.as_bytes(), expected.as_bytes())) {
+        next.run(request).await
+    } else {
+        StatusCode::UNAUTHORIZED.into_response()
+    }
+}
+
+fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right)
+            .fold(0, |acc, (left, right)| acc | (left ^ right))
+            == 0
+}
+
+fn error_response(error: BeatError) -> Response {
+    let status = match error {
+        BeatError::NoQueue
+        | BeatError::NoCurrentTrack
+        | BeatError::NoPreviousTrack
+        | BeatError::NoSuchTrack => StatusCode::NOT_FOUND,
+        BeatError::NoManager | BeatError::Stopping | BeatError::QueueFull => StatusCode::CONFLICT,
+        BeatError::TrackTooLong | BeatError::NotSeekable => StatusCode::UNPROCESSABLE_ENTITY,
+        BeatError::NoValidCommand => StatusCode::BAD_REQUEST,
+        BeatError::Forbidden(_) => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (status, Json(json!({"error": error.to_string()}))).into_response()
+}
+
+async fn guilds(State(state): State<ServerState>) -> Response {
+    let queue_lock = {
+        let guard = state.data.read().await;
+        guard.get::<QueueKey>().cloned()
+    };
+
+    let Some(queue_lock) = queue_lock else {
+        return error_response(BeatError::NoQueues);
+    };
+
+    let maybe_queue = queue_lock.read().await;
+    let guilds = maybe_queue
+        .iter()
+        .filter(|(_, queue)| !queue.queue.is_empty())
+        .map(|(guild_id, queue)| queue_state(*guild_id, queue))
+        .collect::<Vec<Value>>();
+
+    Json(json!({"guilds": guilds})).into_response()
+}
+
+async fn queue(State(state): State<ServerState>, Path(guild_id): Path<GuildId>) -> Response {
+    let queue_lock = {
+        let guard = state.data.read().await;
+        guard.get::<QueueKey>().cloned()
+    };
+
+    let Some(queue_lock) = queue_lock else {
+        return error_response(BeatError::NoQueues);
+    };
+
+    let mut body = {
+        let maybe_queue = queue_lock.read().await;
+        match maybe_queue.get(&guild_id) {
+            Some(queue) => queue_state(guild_id, queue),
+            None => return error_response(BeatError::NoQueue),
+        }
+    };
+
+    if let Ok(manager) = songbird_manager(&state.data).await
+        && let Some(handler_lock) = manager.get(guild_id)
+    {
+        let current = handler_lock.lock().await.queue().current();
+        if let Some(current) = current
+            && let Ok(info) = current.get_info().await
+        {
+            body["position_secs"] = json!(info.position.as_secs());
+        }
+    }
+
+    Json(body).into_response()
+}
+
+#[derive(Deserialize)]
+struct PlayBody {
+    track: String,
+}
+
+async fn play(
+    State(state): State<ServerState>,
+    Path(guild_id): Path<GuildId>,
+    Json(body): Json<PlayBody>,
+) -> Response {
+    let span = info_span!("api", command = "play", guild_id = guild_id.get());
+
+    match append_to_session(&state.data, &state.http, guild_id, body.track)
+        .instrument(span)
+        .await
+    {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(error) => error_response(error),
+    }
+}
+
+async fn action(
+    State(state): State<ServerState>,
+    Path((guild_id, action)): Path<(GuildId, String)>,
+) -> Response {
+    let span = info_span!("api", command = %action, guild_id = guild_id.get());
+
+    let result = async {
+        match action.as_str() {
+            "pause" => pause::toggle(&state.data, &state.http, guild_id).await,
+            "next" => next::skip(&state.data, guild_id).await,
+            "prev" => prev::previous(&state.data, guild_id).await,
+            "loop" => repeat::toggle(&state.data, &state.http, guild_id).await,
+            "stop" => stop::stop(&state.data, &state.http, guild_id).await,
+            _ => Err(BeatError::NoValidCommand),
+        }
+    }
+    .instrument(span)
+    .await;
+
+    match result {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(error) => error_response(error),
+    }
+}
+
+#[derive(Deserialize)]
+struct FeedQuery {
+    guild_id: Option<u64>,
+}
+
+async fn websocket(
+    State(state): State<ServerState>,
+    Query(query): Query<FeedQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let feed = {
+        let guard = state.data.read().await;
+        guard.get::<FeedKey>().cloned()
+    };
+
+    let Some(feed) = feed else {
+        return error_response(BeatError::Other("Feed not initialized"));
+    };
+
+    let filter = query.guild_id.filter(|id| *id != 0).map(GuildId::new);
+
+    upgrade.on_upgrade(move |socket| stream_feed(socket, feed.subscribe(), filter))
+}
+
+async fn stream_feed(
+    mut socket: WebSocket,
+    mut events: broadcast::Receiver<(GuildId, String)>,
+    filter: Option<GuildId>,
+) {
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok((guild_id, event)) => {
+                    if filter.is_some_and(|filter| filter != guild_id) {
+                        continue;
+                    }
+                    if socket.send(Message::Text(event.into())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => warn!(skipped, "WebSocket client lagging"),
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+
+    debug!("WebSocket client disconnected");
+}
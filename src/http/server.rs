@@ -0,0 +1,99 @@
+use crate::http::api;
+use crate::telemetry::health::{Health, Report};
+use crate::telemetry::metrics;
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::json;
+use serenity::http::Http;
+use serenity::prelude::TypeMap;
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+
+#[derive(Clone)]
+pub struct ServerState {
+    pub health: Arc<Health>,
+    pub data: Arc<RwLock<TypeMap>>,
+    pub http: Arc<Http>,
+    pub api_token: Option<String>,
+}
+
+/// Starts the local HTTP server when `BEAT_HTTP_ADDR` is set, e.g. `127.0.0.1:9100`.
+///
+/// The control API under `/api` is only mounted when `BEAT_API_TOKEN` is set as well.
+pub fn spawn(health: Arc<Health>, data: Arc<RwLock<TypeMap>>, http: Arc<Http>) {
+    let Ok(addr) = env::var("BEAT_HTTP_ADDR") else {
+        return;
+    };
+
+    let Ok(addr) = addr.parse::<SocketAddr>() else {
+        warn!(%addr, "Invalid BEAT_HTTP_ADDR, HTTP server disabled");
+        return;
+    };
+
+    let state = ServerState {
+        health,
+        data,
+        http,
+        api_token: env::var("BEAT_API_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty()),
+    };
+
+    tokio::spawn(async move {
+        let mut router = Router::new()
+            .route("/metrics", get(metrics_handler))
+            .route("/health/live", get(live_handler))
+            .route("/health/ready", get(ready_handler));
+
+        if state.api_token.is_some() {
+            router = router.nest("/api", api::router(state.clone()));
+        }
+
+        let router = router.with_state(state);
+
+        match TcpListener::bind(addr).await {
+            Ok(listener) => {
+                info!(%addr, "HTTP server listening");
+
+                axum::serve(listener, router)
+                    .await
+                    .map_err(|error| error!(?error, "HTTP server ended"))
+                    .unwrap_or_default();
+            }
+            Err(error) => error!(?error, %addr, "Failed to bind HTTP server"),
+        }
+    });
+}
+
+async fn metrics_handler() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(),
+    )
+}
+
+async fn live_handler(State(state): State<ServerState>) -> impl IntoResponse {
+    health_response(state.health.report().await, Report::is_live)
+}
+
+async fn ready_handler(State(state): State<ServerState>) -> impl IntoResponse {
+    health_response(state.health.report().await, Report::is_ready)
+}
+
+fn health_response(report: Option<Report>, check: fn(&Report) -> bool) -> impl IntoResponse {
+    match report {
+        Some(report) if check(&report) => (StatusCode::OK, Json(report.to_json())),
+        Some(report) => (StatusCode::SERVICE_UNAVAILABLE, Json(report.to_json())),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"live": false, "ready": false})),
+        ),
+    }
+}
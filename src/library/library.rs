@@ -0,0 +1,250 @@
+use crate::audio::direct::AUDIO_EXTENSIONS;
+use crate::audio::source::describe;
+use serenity::prelude::{TypeMap, TypeMapKey};
+use songbird::input::{AuxMetadata, File};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+use tracing::{info, info_span, warn};
+use tracing_futures::Instrument;
+use url::Url;
+
+/// Index of the local music library.
+pub struct LibraryKey;
+
+impl TypeMapKey for LibraryKey {
+    type Value = Arc<RwLock<Library>>;
+}
+
+#[derive(Debug, Default)]
+pub struct Library {
+    /// Seconds since the Unix epoch of the last scan, pickers of older scans are stale
+    pub scanned_at: u64,
+    /// Tags of every file, linked by its `file://` URL, in path order
+    pub tracks: Vec<AuxMetadata>,
+}
+
+/// Tag searched by `/library search`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tag {
+    Any,
+    Title,
+    Artist,
+    Album,
+}
+
+impl Tag {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "title" => Self::Title,
+            "artist" => Self::Artist,
+            "album" => Self::Album,
+            _ => Self::Any,
+        }
+    }
+
+    fn values(self, track: &AuxMetadata) -> Vec<&str> {
+        let (title, artist, album) = (
+            track.title.as_deref(),
+            track.artist.as_deref(),
+            track.album.as_deref(),
+        );
+
+        match self {
+            Self::Any => vec![title, artist, album],
+            Self::Title => vec![title],
+            Self::Artist => vec![artist],
+            Self::Album => vec![album],
+        }
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+impl Library {
+    /// Tracks whose `tag` holds every word of `query`, ignoring case, with their index.
+    pub fn search(&self, query: &str, tag: Tag) -> Vec<(usize, &AuxMetadata)> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+
+        self.tracks
+            .iter()
+            .enumerate()
+            .filter(|(_, track)| {
+                let values = tag.values(track).join(" ").to_lowercase();
+                words.iter().all(|word| values.contains(word.as_str()))
+            })
+            .collect()
+    }
+}
+
+/// Directory of the library, set with `BEAT_LIBRARY_DIR`. The library is disabled without it.
+pub fn root() -> Option<PathBuf> {
+    env::var("BEAT_LIBRARY_DIR")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .and_then(|dir| Path::new(&dir).canonicalize().ok())
+}
+
+/// Path of a `file://` link, only when it points inside the library, other files are off limits.
+pub fn local_path(url: &str) -> Option<PathBuf> {
+    let root = root()?;
+    let path = Url::parse(url)
+        .ok()
+        .filter(|url| url.scheme() == "file")?
+        .to_file_path()
+        .ok()?
+        .canonicalize()
+        .ok()?;
+
+    Some(path).filter(|path| path.starts_with(&root) && path.is_file())
+}
+
+/// Indexes the library once at startup, in the background.
+pub fn spawn(data: Arc<RwLock<TypeMap>>) {
+    if root().is_none() {
+        return;
+    }
+
+    tokio::spawn(
+        async move {
+            scan(&data).await;
+        }
+        .instrument(info_span!("library")),
+    );
+}
+
+/// Reads the tags of every audio file of the library, replacing the index. Returns the number of
+/// tracks found, `None` when the library is disabled.
+pub async fn scan(data: &Arc<RwLock<TypeMap>>) -> Option<usize> {
+    let root = root()?;
+    let started = Instant::now();
+
+    let files = tokio::task::spawn_blocking(move || {
+        let mut files = vec![];
+        audio_files(&root, &mut files);
+        files.sort();
+        files
+    })
+    .await
+    .ok()?;
+
+    let mut tracks = Vec::with_capacity(files.len());
+    for path in files {
+        tracks.push(describe_file(&path).await);
+    }
+    let count = tracks.len();
+
+    let library_lock = data.read().await.get::<LibraryKey>().cloned()?;
+    *library_lock.write().await = Library {
+        scanned_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        tracks,
+    };
+
+    info!(tracks = count, elapsed = ?started.elapsed(), "Library indexed");
+    Some(count)
+}
+
+/// Collects the audio files under `dir`. Symbolic links to directories are not followed, they
+/// could loop.
+fn audio_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) => {
+            warn!(?error, ?dir, "Could not read library directory");
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() {
+            audio_files(&path, files);
+        } else if path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// Metadata of a library file, from the index when it was scanned already.
+pub async fn metadata(data: &Arc<RwLock<TypeMap>>, path: &Path) -> AuxMetadata {
+    let url = Url::from_file_path(path).ok().map(String::from);
+
+    if let Some(library_lock) = data.read().await.get::<LibraryKey>().cloned()
+        && let Some(track) = library_lock
+            .read()
+            .await
+            .tracks
+            .iter()
+            .find(|track| track.source_url == url)
+    {
+        return track.clone();
+    }
+
+    describe_file(path).await
+}
+
+/// Tags of a file, titled after the file name when it has none.
+async fn describe_file(path: &Path) -> AuxMetadata {
+    let mut metadata = describe(&mut File::new(path.to_path_buf()))
+        .await
+        .unwrap_or_default();
+
+    metadata.source_url = Url::from_file_path(path).ok().map(String::from);
+    if metadata.title.is_none() {
+        metadata.title = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned());
+    }
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn searches_by_tag() {
+        let track = |title: &str, artist: &str, album: Option<&str>| AuxMetadata {
+            title: Some(String::from(title)),
+            artist: Some(String::from(artist)),
+            album: album.map(String::from),
+            ..AuxMetadata::default()
+        };
+        let library = Library {
+            scanned_at: 0,
+            tracks: vec![
+                track("Around the World", "Daft Punk", Some("Homework")),
+                track("Homework", "Someone Else", None),
+                track("One More Time", "Daft Punk", Some("Discovery")),
+            ],
+        };
+        let indices = |query: &str, tag: Tag| {
+            library
+                .search(query, tag)
+                .into_iter()
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(indices("homework", Tag::Any), [0, 1]);
+        assert_eq!(indices("homework", Tag::Album), [0]);
+        assert_eq!(indices("punk daft", Tag::Artist), [0, 2]);
+        assert_eq!(indices("daft time", Tag::Any), [2]);
+        assert_eq!(indices("daft", Tag::Title), Vec::<usize>::new());
+    }
+}
@@ -0,0 +1,2 @@
+#[allow(clippy::module_inception)]
+pub(crate) mod library;
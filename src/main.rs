@@ -7,19 +7,28 @@
 //! features = ["client", "standard_framework", "voice"]
 //! ```
 
+mod audio;
 mod commands;
 mod errors;
+mod history;
+mod http;
+mod library;
 mod messages;
+mod permissions;
+mod podcast;
+mod settings;
+mod telemetry;
 
 // This trait adds the `register_songbird` and `register_songbird_with` methods
 // to the client builder below, making it easy to install this voice client.
 // The voice client can be retrieved in any command using `songbird::get(ctx).await`.
 use songbird::SerenityInit;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::net::IpAddr;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 // YtDl requests need an HTTP client to operate -- we'll create and store our own.
 use reqwest::Client as HttpClient;
@@ -27,8 +36,19 @@ use reqwest::Client as HttpClient;
 // Import the `Context` to handle commands.
 use serenity::client::Context;
 
+use crate::audio::filters::SharedFilters;
+use crate::audio::loudness::{LoudnessKey, ScanPermitsKey};
+use crate::commands::autocomplete::{Suggestions, SuggestionsKey};
 use crate::errors::errors::BeatError;
-use serenity::all::{Command, GuildId, Interaction, MessageId};
+use crate::history::history::HistoryKey;
+use crate::http::api::FeedKey;
+use crate::library::library::LibraryKey;
+use crate::podcast::podcast::Episode;
+use crate::settings::settings::SettingsKey;
+use crate::telemetry::health::Health;
+use crate::telemetry::{health, logging, metrics};
+use serde_json::json;
+use serenity::all::{ChannelId, Command, GuildId, Interaction, MessageId, UserId};
 use serenity::{
     async_trait,
     client::{Client, EventHandler},
@@ -36,7 +56,10 @@ use serenity::{
     prelude::{GatewayIntents, TypeMapKey},
 };
 use songbird::input::AuxMetadata;
-use tokio::sync::RwLock;
+use songbird::tracks::TrackHandle;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{error, info, info_span, warn};
+use tracing_futures::Instrument;
 
 struct HttpKey;
 
@@ -51,8 +74,76 @@ struct Queue {
     repeat: bool,
     stopping: bool,
     playing_index: usize,
+    position: Duration,
+    channel_id: Option<ChannelId>,
     message_id: Option<MessageId>,
-    queue: Vec<AuxMetadata>,
+    queue: Vec<QueuedTrack>,
+    /// Listeners who voted to skip the current track, and how many votes it takes
+    skip_votes: HashSet<UserId>,
+    votes_needed: usize,
+    /// Track still fading out once the next one took over, see `crossfade`
+    crossfading: Option<TrackHandle>,
+    /// Audio filters of the guild, kept across sessions until reset with `/filter`
+    filters: SharedFilters,
+    /// Tracks autoplayed since a listener last did something, and whether one is being picked
+    autoplayed: usize,
+    autoplaying: bool,
+}
+
+/// Where a queued track came from.
+#[derive(Debug, Clone, PartialEq)]
+enum TrackSource {
+    /// Free text searched on YouTube
+    Search(String),
+    /// Link to the track itself
+    Url,
+    /// Expanded from a YouTube playlist or a saved playlist, by its name
+    Playlist(String),
+    /// Picked by autoplay once the queue ran out
+    Autoplay,
+    /// File of the local library
+    Library,
+    /// Episode of a podcast, resumed where the guild left it
+    Podcast(Episode),
+}
+
+impl TrackSource {
+    pub fn is_search(&self) -> bool {
+        matches!(self, Self::Search(_))
+    }
+
+    pub fn is_autoplay(&self) -> bool {
+        matches!(self, Self::Autoplay)
+    }
+}
+
+/// A queue entry: the resolved track and who asked for it.
+#[derive(Debug, Clone)]
+struct QueuedTrack {
+    metadata: AuxMetadata,
+    /// `None` for tracks added through the HTTP API
+    requester: Option<UserId>,
+    queued_at: SystemTime,
+    source: TrackSource,
+    /// Whether the audio is read straight from the link rather than through yt-dlp
+    direct: bool,
+}
+
+impl QueuedTrack {
+    pub fn new(metadata: AuxMetadata, requester: Option<UserId>, source: TrackSource) -> Self {
+        QueuedTrack {
+            metadata,
+            requester,
+            queued_at: SystemTime::now(),
+            source,
+            direct: false,
+        }
+    }
+
+    /// Whether `user_id` queued this track, for checks such as "only the requester or a DJ".
+    pub fn is_requested_by(&self, user_id: UserId) -> bool {
+        self.requester == Some(user_id)
+    }
 }
 
 impl Queue {
@@ -66,8 +157,15 @@ impl Queue {
         self.pause = default.pause;
         self.stopping = default.stopping;
         self.playing_index = default.playing_index;
+        self.position = default.position;
+        self.channel_id = default.channel_id;
         self.message_id = default.message_id;
         self.queue = default.queue;
+        self.skip_votes = default.skip_votes;
+        self.votes_needed = default.votes_needed;
+        self.crossfading = default.crossfading;
+        self.autoplayed = default.autoplayed;
+        self.autoplaying = default.autoplaying;
     }
     pub fn reset_for_play(&mut self) {
         self.reset();
@@ -83,8 +181,16 @@ impl Default for Queue {
             pause: false,
             stopping: true,
             playing_index: 0,
+            position: Duration::ZERO,
+            channel_id: None,
             message_id: None,
             queue: vec![],
+            skip_votes: HashSet::new(),
+            votes_needed: 0,
+            crossfading: None,
+            filters: SharedFilters::default(),
+            autoplayed: 0,
+            autoplaying: false,
         }
     }
 }
@@ -113,10 +219,12 @@ impl EventHandler for Handler {
     }
 
     async fn ready(&self, ctx: Context, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
+        info!(user = %ready.user.name, "Connected to the gateway");
+        health::notify_ready();
 
         let guild_command = vec![
             Command::create_global_command(&ctx.http, commands::play::register()).await,
+            Command::create_global_command(&ctx.http, commands::playnext::register()).await,
             Command::create_global_command(&ctx.http, commands::pause::register()).await,
             Command::create_global_command(&ctx.http, commands::stop::register()).await,
             Command::create_global_command(&ctx.http, commands::next::register()).await,
@@ -124,19 +232,120 @@ impl EventHandler for Handler {
             Command::create_global_command(&ctx.http, commands::repeat::register()).await,
             Command::create_global_command(&ctx.http, commands::save::register()).await,
             Command::create_global_command(&ctx.http, commands::load::register()).await,
+            Command::create_global_command(&ctx.http, commands::delete::register()).await,
             Command::create_global_command(&ctx.http, commands::list::register()).await,
             Command::create_global_command(&ctx.http, commands::clean::register()).await,
+            Command::create_global_command(&ctx.http, commands::queue::register()).await,
+            Command::create_global_command(&ctx.http, commands::settings::register()).await,
+            Command::create_global_command(&ctx.http, commands::volume::register()).await,
+            Command::create_global_command(&ctx.http, commands::seek::register()).await,
+            Command::create_global_command(&ctx.http, commands::seek::register_forward()).await,
+            Command::create_global_command(&ctx.http, commands::seek::register_rewind()).await,
+            Command::create_global_command(&ctx.http, commands::filter::register()).await,
+            Command::create_global_command(&ctx.http, commands::search::register()).await,
+            Command::create_global_command(&ctx.http, commands::history::register()).await,
+            Command::create_global_command(&ctx.http, commands::library::register()).await,
+            Command::create_global_command(&ctx.http, commands::podcast::register()).await,
         ];
 
-        println!("I created the following global slash command: {guild_command:#?}");
+        for command in guild_command {
+            match command {
+                Ok(command) => info!(command = %command.name, "Registered global slash command"),
+                Err(error) => error!(?error, "Failed to register global slash command"),
+            }
+        }
     }
 
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        let interaction_clone = interaction.clone();
+        let span = match &interaction {
+            Interaction::Command(command) => logging::interaction_span(
+                "command",
+                &command.data.name,
+                command.guild_id,
+                command.channel_id,
+                command.user.id,
+            ),
+            Interaction::Component(component) => logging::interaction_span(
+                "component",
+                &component.data.custom_id,
+                component.guild_id,
+                component.channel_id,
+                component.user.id,
+            ),
+            _ => info_span!("interaction", kind = "other"),
+        };
+
+        async {
+            // Permissions are enforced here once, handlers can assume the caller is allowed
+            if Self::authorize(&ctx, &interaction).await {
+                Self::dispatch(ctx, interaction).await;
+            }
+        }
+        .instrument(span)
+        .await;
+    }
+}
+
+impl Handler {
+    /// Checks the caller against the permission layer, answering them when denied.
+    async fn authorize(ctx: &Context, interaction: &Interaction) -> bool {
+        let Err(denied) = permissions::permissions::check(ctx, interaction).await else {
+            return true;
+        };
+
+        let (id, token, name) = match interaction {
+            Interaction::Command(command) => {
+                (command.id, &command.token, command.data.name.as_str())
+            }
+            Interaction::Component(component) => (
+                component.id,
+                &component.token,
+                component
+                    .data
+                    .custom_id
+                    .split(':')
+                    .next()
+                    .unwrap_or_default(),
+            ),
+            _ => return false,
+        };
+
+        info!(%denied, "Interaction denied");
+        metrics::command(name, false);
+
+        let response = json!({
+            "type": 4,
+            "data": {
+                "content": denied.to_string(),
+                "flags": 64
+            }
+        });
+        ctx.http
+            .create_interaction_response(id, token, &response, vec![])
+            .await
+            .map_err(|error| error!(?error, "Failed to answer denied interaction"))
+            .unwrap_or_default();
+
+        false
+    }
+
+    async fn dispatch(ctx: Context, interaction: Interaction) {
+        let guild_id = match &interaction {
+            Interaction::Command(command) => command.guild_id,
+            Interaction::Component(component) => component.guild_id,
+            _ => None,
+        };
+        if let Some(guild_id) = guild_id {
+            commands::autoplay::listener_acted(&ctx.data, guild_id).await;
+        }
 
-        if let Interaction::Command(command) = interaction_clone {
-            match command.data.name.as_str() {
+        if let Interaction::Command(command) = &interaction {
+            let name = command.data.name.as_str();
+            let result = match name {
                 "play" => commands::play::run(&ctx, &interaction, &command.data.options()).await,
+                "playnext" => {
+                    commands::playnext::run(&ctx, &interaction, &command.data.options()).await
+                }
                 "pause" => commands::pause::run(&ctx, &interaction).await,
                 "stop" => commands::stop::run(&ctx, &interaction).await,
                 "next" => commands::next::run(&ctx, &interaction).await,
@@ -144,38 +353,88 @@ impl EventHandler for Handler {
                 "loop" => commands::repeat::run(&ctx, &interaction).await,
                 "save" => commands::save::run(&ctx, &interaction, &command.data.options()).await,
                 "load" => commands::load::run(&ctx, &interaction, &command.data.options()).await,
+                "delete" => {
+                    commands::delete::run(&ctx, &interaction, &command.data.options()).await
+                }
                 "list" => commands::list::run(&ctx, &interaction).await,
                 "clean" => commands::clean::run(&ctx, &interaction).await,
+                "queue" => commands::queue::run(&ctx, &interaction).await,
+                "settings" => {
+                    commands::settings::run(&ctx, &interaction, &command.data.options()).await
+                }
+                "volume" => {
+                    commands::volume::run(&ctx, &interaction, &command.data.options()).await
+                }
+                "seek" => commands::seek::run(&ctx, &interaction, &command.data.options()).await,
+                "forward" => {
+                    commands::seek::run_relative(&ctx, &interaction, &command.data.options(), 1)
+                        .await
+                }
+                "rewind" => {
+                    commands::seek::run_relative(&ctx, &interaction, &command.data.options(), -1)
+                        .await
+                }
+                "filter" => {
+                    commands::filter::run(&ctx, &interaction, &command.data.options()).await
+                }
+                "search" => {
+                    commands::search::run(&ctx, &interaction, &command.data.options()).await
+                }
+                "history" => commands::history::run(&ctx, &interaction).await,
+                "library" => {
+                    commands::library::run(&ctx, &interaction, &command.data.options()).await
+                }
+                "podcast" => {
+                    commands::podcast::run(&ctx, &interaction, &command.data.options()).await
+                }
                 _ => Err(BeatError::NoValidCommand),
-            }
-            .map_err(|err| {
-                eprintln!("{:?}", err);
-                ()
-            })
-            .unwrap_or(());
-        } else if let Interaction::Component(command) = interaction_clone {
-            match command.data.custom_id.as_str() {
+            };
+
+            metrics::command(name, result.is_ok());
+            result
+                .map(|_| info!("Command handled"))
+                .unwrap_or_else(|error| error!(%error, "Command failed"));
+        } else if let Interaction::Component(command) = &interaction {
+            // Stateful components carry their arguments after the name, e.g. `queue:next:0:0`
+            let name = command.data.custom_id.split(':').next().unwrap_or_default();
+            let result = match name {
                 "pause" => commands::pause::run(&ctx, &interaction).await,
                 "stop" => commands::stop::run(&ctx, &interaction).await,
                 "next" => commands::next::run(&ctx, &interaction).await,
                 "prev" => commands::prev::run(&ctx, &interaction).await,
                 "loop" => commands::repeat::run(&ctx, &interaction).await,
+                "queue" => commands::queue::run(&ctx, &interaction).await,
+                "jump" => commands::jump::run(&ctx, &interaction).await,
+                "volume" => commands::volume::run(&ctx, &interaction, &[]).await,
+                "forward" => commands::seek::run_relative(&ctx, &interaction, &[], 1).await,
+                "rewind" => commands::seek::run_relative(&ctx, &interaction, &[], -1).await,
+                "search" => commands::search::run(&ctx, &interaction, &[]).await,
+                "history" => commands::history::run(&ctx, &interaction).await,
+                "library" => commands::library::run(&ctx, &interaction, &[]).await,
+                "podcast" => commands::podcast::run(&ctx, &interaction, &[]).await,
                 _ => Err(BeatError::NoValidCommand),
-            }
-            .map_err(|err| {
-                eprintln!("{:?}", err);
-                ()
-            })
-            .unwrap_or(());
-        } else {
+            };
+
+            metrics::command(name, result.is_ok());
+            result
+                .map(|_| info!("Component handled"))
+                .unwrap_or_else(|error| error!(%error, "Component failed"));
+        } else if let Interaction::Autocomplete(autocomplete) = &interaction {
+            let result = match autocomplete.data.name.as_str() {
+                "play" | "playnext" => commands::autocomplete::run(&ctx, &interaction).await,
+                _ => Err(BeatError::NoValidCommand),
+            };
+
+            // Suggestions come with every keystroke, only failures are worth logging
+            result.unwrap_or_else(|error| warn!(%error, "Autocomplete failed"));
         }
     }
 }
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
     dotenv::dotenv().ok();
+    logging::init();
 
     // Configure the client with your Discord bot token in the environment.
     let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
@@ -192,10 +451,26 @@ async fn main() {
                 .unwrap(),
         )
         .type_map_insert::<QueueKey>(Arc::new(RwLock::new(HashMap::new())))
+        .type_map_insert::<FeedKey>(http::api::feed())
+        .type_map_insert::<SettingsKey>(Arc::new(RwLock::new(HashMap::new())))
+        .type_map_insert::<LoudnessKey>(Arc::new(RwLock::new(HashMap::new())))
+        .type_map_insert::<ScanPermitsKey>(audio::loudness::scan_permits())
+        .type_map_insert::<SuggestionsKey>(Arc::new(Mutex::new(Suggestions::default())))
+        .type_map_insert::<HistoryKey>(Arc::new(Mutex::new(vec![])))
+        .type_map_insert::<LibraryKey>(Arc::new(RwLock::new(Default::default())))
         .register_songbird()
         .await
         .expect("Error creating client");
 
+    let health = Arc::new(Health::new(
+        client.shard_manager.clone(),
+        client.data.clone(),
+    ));
+    health::spawn(health.clone());
+    http::server::spawn(health, client.data.clone(), client.http.clone());
+    messages::progress::spawn(client.data.clone(), client.http.clone());
+    library::library::spawn(client.data.clone());
+
     // Finally, start a single shard, and start listening to events.
     //
     // Shards will automatically attempt to reconnect, and will perform exponential backoff until
@@ -204,9 +479,9 @@ async fn main() {
         let _ = client
             .start()
             .await
-            .map_err(|why| println!("Client ended: {:?}", why));
+            .map_err(|why| error!(?why, "Client ended"));
     });
 
     let _signal_err = tokio::signal::ctrl_c().await;
-    println!("Received Ctrl-C, shutting down.");
+    info!("Received Ctrl-C, shutting down");
 }
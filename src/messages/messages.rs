@@ -1,10 +1,68 @@
-use crate::Queue;
+use crate::errors::errors::BeatError;
+use crate::history::history::Entry;
+use crate::podcast::podcast::{Episode, Subscription, short_id};
+use crate::settings::settings::{Language, MAX_VOLUME, MIN_VOLUME, Settings, Verbosity};
+use crate::{Queue, QueuedTrack};
 use serde_json::json;
+use serenity::http::Http;
 use serenity::json::Value;
+use songbird::input::AuxMetadata;
 use std::cmp::{max, min};
+use std::sync::PoisonError;
 use std::time::Duration;
 
-pub(crate) fn to_embed(queue: &Queue) -> Value {
+const LIVE: &str = "LIVE";
+const UNKNOWN_DURATION: &str = "--:--";
+/// Discord's default avatar, shown for tracks without artwork such as direct audio links.
+const PLACEHOLDER_THUMBNAIL: &str = "https://cdn.discordapp.com/embed/avatars/0.png";
+/// Wording of the now-playing message.
+struct Strings {
+    now_playing: &'static str,
+    requested_by: &'static str,
+    vote_to_skip: &'static str,
+    of: &'static str,
+    tracks: &'static str,
+    left: &'static str,
+    jump_to_track: &'static str,
+    autoplay: &'static str,
+}
+
+const ENGLISH: Strings = Strings {
+    now_playing: "Now playing",
+    requested_by: "Requested by",
+    vote_to_skip: "Vote to skip",
+    of: "of",
+    tracks: "tracks",
+    left: "left",
+    jump_to_track: "Jump to track...",
+    autoplay: "Autoplay",
+};
+
+const FRENCH: Strings = Strings {
+    now_playing: "En cours de lecture",
+    requested_by: "Demandé par",
+    vote_to_skip: "Vote pour passer",
+    of: "sur",
+    tracks: "pistes",
+    left: "restant",
+    jump_to_track: "Aller à la piste...",
+    autoplay: "Lecture automatique",
+};
+
+fn strings(language: Language) -> &'static Strings {
+    match language {
+        Language::English => &ENGLISH,
+        Language::French => &FRENCH,
+    }
+}
+
+/// Discord's maximum number of options in a select menu.
+const JUMP_OPTIONS: usize = 25;
+/// Previous tracks listed before the current one in the jump menu.
+const JUMP_PREVIOUS: usize = 5;
+
+pub(crate) fn to_embed(queue: &Queue, settings: &Settings) -> Value {
+    let text = strings(settings.language);
     let whole_queue = queue.queue.clone();
     let loop_mode = if queue.repeat { 3 } else { 2 };
     let (pause_mode, pause_button) = if queue.pause {
@@ -12,63 +70,133 @@ pub(crate) fn to_embed(queue: &Queue) -> Value {
     } else {
         (1, "⏸️")
     };
-    let current_track = whole_queue.get(queue.playing_index).unwrap().clone();
-    let title = current_track.title.unwrap();
-    let artist = current_track.artist.unwrap();
-    let duration = readable_duration(current_track.duration.unwrap());
-    let link = current_track.source_url.unwrap();
-    let thumbnail = current_track.thumbnail.unwrap();
-    let (played, to_play) = whole_queue.split_at(queue.playing_index);
-
-    let time_to_play = readable_duration(
-        to_play
-            .iter()
-            .map(|played| played.duration.unwrap())
-            .fold(Duration::from_secs(0), |acc, duration| acc + duration),
-    );
-    let time_elapsed = played
-        .iter()
-        .map(|played| played.duration.unwrap())
-        .fold(Duration::from_secs(0), |acc, duration| acc + duration);
-    let total_time = whole_queue
-        .iter()
-        .map(|played| played.duration.unwrap())
-        .fold(Duration::from_secs(0), |acc, duration| acc + duration);
+    let current_entry = whole_queue.get(queue.playing_index);
+    let current_track = current_entry
+        .map(|entry| entry.metadata.clone())
+        .unwrap_or_default();
+    let title = track_title(&current_track);
+    let artist = track_artist(&current_track);
+    let duration = readable_track_duration(&current_track);
+    let position = match current_track.duration {
+        Some(track_duration) => queue.position.min(track_duration),
+        None => queue.position,
+    };
+    // Livestreams can't be seeked
+    let seekable = current_track.duration.is_some();
+    let (played, to_play) = whole_queue.split_at(queue.playing_index.min(whole_queue.len()));
 
-    let elapsed_over_total = readable_elapsed(time_elapsed, total_time);
+    // A single livestream makes every total unknown
+    let time_to_play = total_duration(to_play)
+        .map(|to_play| readable_duration(to_play.saturating_sub(position)))
+        .unwrap_or(String::from(UNKNOWN_DURATION));
+    let elapsed_over_total = match (total_duration(played), total_duration(&whole_queue)) {
+        (Some(played), Some(total)) => readable_elapsed(played + position, total),
+        _ => format!(
+            "{}/{}",
+            total_duration(played)
+                .map(|played| readable_duration(played + position))
+                .unwrap_or(String::from(UNKNOWN_DURATION)),
+            UNKNOWN_DURATION
+        ),
+    };
 
     let short_queue: Vec<String> = queue
         .queue
         .iter()
-        .map(|track| {
+        .map(|entry| {
+            let requester = match (settings.verbosity, entry.requester) {
+                (_, None) if entry.source.is_autoplay() => String::from(" · 📻"),
+                (Verbosity::Detailed, Some(requester)) => format!(" · <@{}>", requester),
+                _ => String::new(),
+            };
+
             format!(
-                "{} ({}) - {}",
-                track.title.clone().unwrap(),
-                readable_duration(track.duration.unwrap()),
-                track.artist.clone().unwrap()
+                "{} ({}) - {}{}",
+                track_title(&entry.metadata),
+                readable_track_duration(&entry.metadata),
+                track_artist(&entry.metadata),
+                requester
             )
         })
         .collect();
 
-    let short = get_short_playlist(queue.playing_index, &short_queue, 2).join("\n");
+    let short = match settings.verbosity {
+        Verbosity::Compact => String::new(),
+        _ => get_short_playlist(queue.playing_index, &short_queue, settings.preview_tracks)
+            .join("\n"),
+    };
+
+    let progress = match current_track.duration {
+        Some(track_duration) => format!(
+            "{} `{}`",
+            progress_bar(position, track_duration, 18),
+            readable_elapsed(position, track_duration)
+        ),
+        None => format!("🔴 **{}** `{}`", LIVE, readable_duration(position)),
+    };
+    let volume_emoji = if settings.default_volume == 0 {
+        "🔇"
+    } else {
+        "🔊"
+    };
+    let progress = format!(
+        "{} · {} {}%",
+        progress, volume_emoji, settings.default_volume
+    );
+    let filters = *queue.filters.read().unwrap_or_else(PoisonError::into_inner);
+    let progress = match filters.describe() {
+        Some(filters) => format!("{} · 🎛️ {}", progress, filters),
+        None => progress,
+    };
+    let progress = match (
+        settings.verbosity,
+        current_entry.and_then(|entry| entry.requester),
+    ) {
+        (Verbosity::Compact, _) | (_, None) => progress,
+        (_, Some(requester)) => format!("{}\n{} <@{}>", progress, text.requested_by, requester),
+    };
+    let progress = if current_entry.is_some_and(|entry| entry.source.is_autoplay()) {
+        format!("{}\n📻 {}", progress, text.autoplay)
+    } else {
+        progress
+    };
+    let progress = if queue.skip_votes.is_empty() {
+        progress
+    } else {
+        format!(
+            "{}\n⏭ {}: {}/{}",
+            progress,
+            text.vote_to_skip,
+            queue.skip_votes.len(),
+            queue.votes_needed
+        )
+    };
+
+    let mut embed = json!({
+      "author": {
+        "name": format!("🔊 {}", text.now_playing)
+      },
+      "title": format!("**{} ({}) - {}**", title, duration, artist),
+      "description": format!("{}\n\n{}", progress, short).trim_end(),
+      "thumbnail": {
+        "url": current_track.thumbnail.clone().unwrap_or(String::from(PLACEHOLDER_THUMBNAIL)),
+      },
+      "footer": {
+        "text": format!("{} {} {} {} - {} ({} {})", queue.playing_index + 1, text.of, whole_queue.len(), text.tracks, elapsed_over_total, time_to_play, text.left),
+      }
+    });
+
+    // Discord rejects a null URL and any but web links, such as the files of the library
+    if let Some(link) = current_track
+        .source_url
+        .clone()
+        .filter(|link| link.starts_with("https://") || link.starts_with("http://"))
+    {
+        embed["url"] = json!(link);
+    }
 
     let json = json!({
-      "embeds": [
-        {
-          "author": {
-            "name": "🔊 Now playing"
-          },
-          "title": format!("**{} ({}) - {}**", title, duration, artist),
-          "description": short,
-          "url": link,
-          "thumbnail": {
-            "url": thumbnail,
-          },
-          "footer": {
-            "text": format!("{} of {} tracks - {} ({} left)", queue.playing_index + 1, whole_queue.len(), elapsed_over_total, time_to_play),
-          }
-        }
-      ],
+      "embeds": [embed],
       "components": [
         {
           "type": 1,
@@ -114,6 +242,60 @@ pub(crate) fn to_embed(queue: &Queue) -> Value {
               "custom_id": "loop"
             }
           ]
+        },
+        {
+          "type": 1,
+          "components": [
+            {
+              "type": 2,
+              "emoji": {
+                "name": "⏪"
+              },
+              "label": "10s",
+              "style": 2,
+              "custom_id": "rewind",
+              "disabled": !seekable
+            },
+            {
+              "type": 2,
+              "emoji": {
+                "name": "⏩"
+              },
+              "label": "10s",
+              "style": 2,
+              "custom_id": "forward",
+              "disabled": !seekable
+            },
+            {
+              "type": 2,
+              "emoji": {
+                "name": "🔉"
+              },
+              "style": 2,
+              "custom_id": "volume:down",
+              "disabled": settings.default_volume == MIN_VOLUME
+            },
+            {
+              "type": 2,
+              "emoji": {
+                "name": "🔊"
+              },
+              "style": 2,
+              "custom_id": "volume:up",
+              "disabled": settings.default_volume >= MAX_VOLUME
+            }
+          ]
+        },
+        {
+          "type": 1,
+          "components": [
+            {
+              "type": 3,
+              "custom_id": "jump",
+              "placeholder": text.jump_to_track,
+              "options": jump_options(queue)
+            }
+          ]
         }
       ]
     });
@@ -121,6 +303,437 @@ pub(crate) fn to_embed(queue: &Queue) -> Value {
     json
 }
 
+/// Options of the jump select menu, a window of previous and upcoming tracks around the current
+/// one since Discord caps select menus at 25 options.
+fn jump_options(queue: &Queue) -> Vec<Value> {
+    let start = queue.playing_index.saturating_sub(JUMP_PREVIOUS);
+    let end = min(queue.queue.len(), start + JUMP_OPTIONS);
+    let start = end.saturating_sub(JUMP_OPTIONS);
+
+    (start..end)
+        .map(|index| {
+            let track = &queue.queue[index].metadata;
+            json!({
+                "label": truncate(&format!("{}. {}", index + 1, track_title(track)), 100),
+                "description": truncate(
+                    &format!("{} - {}", readable_track_duration(track), track_artist(track)),
+                    100
+                ),
+                "value": index.to_string(),
+                "default": index == queue.playing_index,
+            })
+        })
+        .collect()
+}
+
+/// Cuts a string to Discord's component text limit, counted in characters.
+pub(crate) fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        String::from(text)
+    } else {
+        let mut truncated: String = text.chars().take(limit - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+/// One page of the full queue, with navigation buttons carrying the page and the time it was
+/// rendered so stale pages can be expired.
+pub(crate) fn to_queue_page(queue: &Queue, page: usize, page_size: usize, issued: u64) -> Value {
+    let pages = queue.queue.len().div_ceil(page_size).max(1);
+    let page = page.min(pages - 1);
+    let start = page * page_size;
+
+    let lines: Vec<String> = queue
+        .queue
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(page_size)
+        .map(|(i, entry)| {
+            let marker = if i == queue.playing_index && !queue.queue.is_empty() {
+                "▶️"
+            } else {
+                "-"
+            };
+            let requester = match entry.requester {
+                Some(requester) => format!(" · <@{}>", requester),
+                None if entry.source.is_autoplay() => String::from(" · 📻"),
+                None => String::new(),
+            };
+
+            format!(
+                "{} {}. {} ({}) - {}{}",
+                marker,
+                i + 1,
+                track_title(&entry.metadata),
+                readable_track_duration(&entry.metadata),
+                track_artist(&entry.metadata),
+                requester
+            )
+        })
+        .collect();
+
+    let description = if lines.is_empty() {
+        String::from("_Nothing queued_")
+    } else {
+        lines.join("\n")
+    };
+
+    let button = |action: &str, emoji: &str, disabled: bool| {
+        json!({
+          "type": 2,
+          "emoji": {
+            "name": emoji
+          },
+          "style": 2,
+          "custom_id": format!("queue:{}:{}:{}", action, page, issued),
+          "disabled": disabled
+        })
+    };
+
+    json!({
+      "embeds": [
+        {
+          "title": "**Queue**",
+          "description": description,
+          "footer": {
+            "text": format!("Page {} of {} - {} tracks", page + 1, pages, queue.queue.len()),
+          }
+        }
+      ],
+      "components": [
+        {
+          "type": 1,
+          "components": [
+            button("first", "⏪", page == 0),
+            button("prev", "◀️", page == 0),
+            button("next", "▶️", page + 1 >= pages),
+            button("last", "⏩", page + 1 >= pages),
+          ]
+        }
+      ]
+    })
+}
+
+/// One page of the play history, newest first, with navigation buttons carrying the page and the
+/// time it was rendered, and a button per entry to queue it again.
+pub(crate) fn to_history_page(
+    entries: &[Entry],
+    page: usize,
+    page_size: usize,
+    issued: u64,
+) -> Value {
+    let pages = entries.len().div_ceil(page_size).max(1);
+    let page = page.min(pages - 1);
+    let start = page * page_size;
+    let shown = &entries[start.min(entries.len())..(start + page_size).min(entries.len())];
+
+    let lines: Vec<String> = shown
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let requester = entry
+                .requester
+                .map(|requester| format!(" · <@{}>", requester))
+                .unwrap_or_default();
+
+            format!(
+                "{}. {} - {} · <t:{}:R> · {}/{}{}",
+                start + i + 1,
+                entry.title.as_deref().unwrap_or(&entry.url),
+                entry.artist.as_deref().unwrap_or("Unknown artist"),
+                entry.started_at / 1000,
+                readable_duration(Duration::from_secs(entry.listened_secs)),
+                entry
+                    .duration_secs
+                    .map(|secs| readable_duration(Duration::from_secs(secs)))
+                    .unwrap_or(String::from(LIVE)),
+                requester
+            )
+        })
+        .collect();
+
+    let description = if lines.is_empty() {
+        String::from("_Nothing played yet_")
+    } else {
+        lines.join("\n")
+    };
+
+    let button = |action: &str, emoji: &str, disabled: bool| {
+        json!({
+          "type": 2,
+          "emoji": {
+            "name": emoji
+          },
+          "style": 2,
+          "custom_id": format!("history:{}:{}:{}", action, page, issued),
+          "disabled": disabled
+        })
+    };
+
+    let mut components = vec![json!({
+      "type": 1,
+      "components": [
+        button("first", "⏪", page == 0),
+        button("prev", "◀️", page == 0),
+        button("next", "▶️", page + 1 >= pages),
+        button("last", "⏩", page + 1 >= pages),
+      ]
+    })];
+
+    // Entries are found back by their start time, which stays valid as the history grows
+    let requeue: Vec<Value> = shown
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            json!({
+              "type": 2,
+              "emoji": {
+                "name": "🔁"
+              },
+              "label": format!("{}", start + i + 1),
+              "style": 2,
+              "custom_id": format!("history:play:{}", entry.started_at),
+            })
+        })
+        .collect();
+    components.extend(
+        requeue
+            .chunks(5)
+            .map(|row| json!({"type": 1, "components": row})),
+    );
+
+    json!({
+      "embeds": [
+        {
+          "title": "**History**",
+          "description": description,
+          "footer": {
+            "text": format!("Page {} of {} - {} tracks", page + 1, pages, entries.len()),
+          }
+        }
+      ],
+      "components": components
+    })
+}
+
+/// Results of a search to pick from, in a select menu carrying the time it was rendered so
+/// stale searches can be expired.
+pub(crate) fn to_search_results(query: &str, results: &[AuxMetadata], issued: u64) -> Value {
+    let options: Vec<Value> = results
+        .iter()
+        .filter_map(|track| {
+            // The link is what gets queued, Discord caps values at 100 characters
+            let link = track.source_url.clone().filter(|link| link.len() <= 100)?;
+            Some(json!({
+                "label": truncate(&track_title(track), 100),
+                "description": truncate(
+                    &format!("{} - {}", readable_track_duration(track), track_artist(track)),
+                    100
+                ),
+                "value": link,
+            }))
+        })
+        .collect();
+
+    if options.is_empty() {
+        return json!({
+            "content": format!("_No results for **{}**._", truncate(query, 100)),
+            "components": []
+        });
+    }
+
+    json!({
+      "content": format!("Results for **{}**:", truncate(query, 100)),
+      "components": [
+        {
+          "type": 1,
+          "components": [
+            {
+              "type": 3,
+              "custom_id": format!("search:{}", issued),
+              "placeholder": "Pick a track to queue...",
+              "options": options
+            }
+          ]
+        }
+      ]
+    })
+}
+
+/// Picker of `/library search` results, valued by their index in the library scanned at
+/// `scanned_at`. Discord shows at most 25 options.
+pub(crate) fn to_library_results(
+    query: &str,
+    results: &[(usize, &AuxMetadata)],
+    scanned_at: u64,
+    issued: u64,
+) -> Value {
+    if results.is_empty() {
+        return json!({
+            "content": format!("_No files match **{}**._", truncate(query, 100)),
+            "components": []
+        });
+    }
+
+    let options: Vec<Value> = results
+        .iter()
+        .take(25)
+        .map(|(index, track)| {
+            let album = track
+                .album
+                .as_deref()
+                .map(|album| format!(" · {}", album))
+                .unwrap_or_default();
+            json!({
+                "label": truncate(&track_title(track), 100),
+                "description": truncate(
+                    &format!("{} - {}{}", readable_track_duration(track), track_artist(track), album),
+                    100
+                ),
+                "value": index.to_string(),
+            })
+        })
+        .collect();
+    let more = if results.len() > options.len() {
+        format!(" (first {} of {})", options.len(), results.len())
+    } else {
+        String::new()
+    };
+
+    json!({
+      "content": format!("Library files matching **{}**{}:", truncate(query, 100), more),
+      "components": [
+        {
+          "type": 1,
+          "components": [
+            {
+              "type": 3,
+              "custom_id": format!("library:{}:{}", scanned_at, issued),
+              "placeholder": "Pick a file to queue...",
+              "options": options
+            }
+          ]
+        }
+      ]
+    })
+}
+
+/// Picker of `/podcast episodes`, valued by the short ids of the feed and of the episode. Episodes
+/// started already tell where they resume.
+pub(crate) fn to_podcast_episodes(
+    episodes: &[(&Subscription, &Episode, Option<Duration>)],
+    issued: u64,
+) -> Value {
+    if episodes.is_empty() {
+        return json!({
+            "content": "_No episodes to play, follow a podcast with `/podcast subscribe`._",
+            "components": []
+        });
+    }
+
+    let options: Vec<Value> = episodes
+        .iter()
+        .take(25)
+        .map(|(subscription, episode, position)| {
+            let description = [
+                Some(subscription.title.clone()),
+                episode.duration.map(readable_duration),
+                position.map(|position| format!("resumes at {}", readable_duration(position))),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" · ");
+
+            json!({
+                "label": truncate(episode.title.as_deref().unwrap_or(&episode.url), 100),
+                "description": truncate(&description, 100),
+                "value": format!("{}:{}", short_id(&subscription.url), short_id(&episode.url)),
+            })
+        })
+        .collect();
+
+    json!({
+      "content": "Latest episodes:",
+      "components": [
+        {
+          "type": 1,
+          "components": [
+            {
+              "type": 3,
+              "custom_id": format!("podcast:{}", issued),
+              "placeholder": "Pick an episode to queue...",
+              "options": options
+            }
+          ]
+        }
+      ]
+    })
+}
+
+/// Edits the queue message in place, if one was sent.
+pub(crate) async fn update_message(
+    http: &Http,
+    queue: &Queue,
+    settings: &Settings,
+) -> Result<(), BeatError> {
+    if let (Some(channel_id), Some(message_id)) = (queue.channel_id, queue.message_id) {
+        http.edit_message(channel_id, message_id, &to_embed(queue, settings), vec![])
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Title of a track, falling back to its tag title then its URL.
+pub(crate) fn track_title(track: &AuxMetadata) -> String {
+    track
+        .title
+        .clone()
+        .or(track.track.clone())
+        .or(track.source_url.clone())
+        .unwrap_or(String::from("Unknown track"))
+}
+
+/// Artist of a track, falling back to the uploader for uploads without an artist.
+pub(crate) fn track_artist(track: &AuxMetadata) -> String {
+    track
+        .artist
+        .clone()
+        .or(track.channel.clone())
+        .unwrap_or(String::from("Unknown artist"))
+}
+
+/// Duration of a track, or `LIVE` for livestreams which have none.
+pub(crate) fn readable_track_duration(track: &AuxMetadata) -> String {
+    track
+        .duration
+        .map(readable_duration)
+        .unwrap_or(String::from(LIVE))
+}
+
+/// Sum of the track durations, unknown as soon as one of them is.
+fn total_duration(tracks: &[QueuedTrack]) -> Option<Duration> {
+    tracks
+        .iter()
+        .map(|entry| entry.metadata.duration)
+        .try_fold(Duration::ZERO, |acc, duration| Some(acc + duration?))
+}
+
+/// Text progress bar such as `▬▬▬▬🔘▬▬▬▬▬`, with the knob placed at `position`.
+fn progress_bar(position: Duration, total: Duration, width: usize) -> String {
+    let knob = if total.is_zero() {
+        0
+    } else {
+        ((position.as_secs_f64() / total.as_secs_f64()) * width as f64) as usize
+    }
+    .min(width - 1);
+
+    format!("{}🔘{}", "▬".repeat(knob), "▬".repeat(width - 1 - knob))
+}
+
 fn readable_duration(duration: Duration) -> String {
     let seconds = duration.as_secs() % 60;
     let minutes = (duration.as_secs() / 60) % 60;
@@ -164,7 +777,7 @@ fn readable_elapsed(elapsed: Duration, total: Duration) -> String {
     format!("{}/{}", parts_elapsed.join(":"), parts_total.join(":"))
 }
 
-pub fn get_short_playlist<'a>(index: usize, data: &'a [String], split: usize) -> Vec<String> {
+pub fn get_short_playlist(index: usize, data: &[String], split: usize) -> Vec<String> {
     let len = data.len();
     if len == 0 {
         return vec![];
@@ -174,11 +787,11 @@ pub fn get_short_playlist<'a>(index: usize, data: &'a [String], split: usize) ->
 
     // If there is less to display than the minimum, display all
     if data.len() <= split * 2 + 1 {
-        for i in 0..data.len() {
+        for (i, track) in data.iter().enumerate() {
             if i == index {
-                result.push(format!("▶️ {}. {}", i + 1, data[i]));
+                result.push(format!("▶️ {}. {}", i + 1, track));
             } else {
-                result.push(format!("- {}. {}", i + 1, data[i]));
+                result.push(format!("- {}. {}", i + 1, track));
             }
         }
 
@@ -193,13 +806,13 @@ pub fn get_short_playlist<'a>(index: usize, data: &'a [String], split: usize) ->
 
     // Readjust before
     if (index.checked_sub(before)).is_none() {
-        after = before - index + after;
+        after += before - index;
         before = index;
     }
 
     // Readjust after
     if (index + after) >= data.len() - 1 {
-        before = after - (data.len() - 1 - index) + before;
+        before += after - (data.len() - 1 - index);
         after = data.len() - 1 - index;
     }
 
@@ -207,8 +820,9 @@ pub fn get_short_playlist<'a>(index: usize, data: &'a [String], split: usize) ->
     if index > 0 && before > 0 {
         result.push(format!("- {}. {}", 1, &data[0]));
 
-        for i in max(index - before, 1)..index {
-            result.push(format!("- {}. {}", i + 1, &data[i]));
+        let first = max(index - before, 1);
+        for (i, track) in data.iter().enumerate().take(index).skip(first) {
+            result.push(format!("- {}. {}", i + 1, track));
         }
     }
 
@@ -217,8 +831,9 @@ pub fn get_short_playlist<'a>(index: usize, data: &'a [String], split: usize) ->
 
     // Pick elements before the index
     if index < data.len() - 1 && after < data.len() - 1 {
-        for i in index + 1..min(index + after + 1, data.len() - 1) {
-            result.push(format!("- {}. {}", i + 1, &data[i]));
+        let last = min(index + after + 1, data.len() - 1);
+        for (i, track) in data.iter().enumerate().take(last).skip(index + 1) {
+            result.push(format!("- {}. {}", i + 1, track));
         }
 
         result.push(format!("- {}. {}", data.len(), &data[data.len() - 1]));
@@ -226,3 +841,156 @@ pub fn get_short_playlist<'a>(index: usize, data: &'a [String], split: usize) ->
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TrackSource;
+    use serenity::all::UserId;
+
+    #[test]
+    fn renders_tracks_without_metadata() {
+        let mut queue = Queue::default();
+        queue.queue.push(QueuedTrack::new(
+            AuxMetadata {
+                channel: Some(String::from("Uploader")),
+                source_url: Some(String::from("https://example.com/live")),
+                ..Default::default()
+            },
+            None,
+            TrackSource::Url,
+        ));
+
+        let embed = to_embed(&queue, &Settings::default());
+
+        assert_eq!(
+            embed["embeds"][0]["title"],
+            "**https://example.com/live (LIVE) - Uploader**"
+        );
+        assert_eq!(
+            embed["embeds"][0]["footer"]["text"],
+            "1 of 1 tracks - 00/--:-- (--:-- left)"
+        );
+        assert_eq!(
+            embed["embeds"][0]["thumbnail"]["url"],
+            PLACEHOLDER_THUMBNAIL
+        );
+    }
+
+    #[test]
+    fn links_only_web_tracks() {
+        let mut queue = Queue::default();
+        queue.queue.push(QueuedTrack::new(
+            AuxMetadata {
+                title: Some(String::from("Local song")),
+                source_url: Some(String::from("file:///srv/music/song.flac")),
+                duration: Some(Duration::from_secs(180)),
+                ..Default::default()
+            },
+            None,
+            TrackSource::Library,
+        ));
+
+        let embed = to_embed(&queue, &Settings::default());
+
+        assert!(embed["embeds"][0].get("url").is_none());
+        assert_eq!(
+            embed["embeds"][0]["title"],
+            "**Local song (03:00) - Unknown artist**"
+        );
+    }
+
+    #[test]
+    fn queue_page_clamps_and_marks_current() {
+        let mut queue = Queue::default();
+        for i in 0..12 {
+            queue.queue.push(QueuedTrack::new(
+                AuxMetadata {
+                    title: Some(format!("Track {}", i + 1)),
+                    duration: Some(Duration::from_secs(60)),
+                    ..Default::default()
+                },
+                (i == 11).then_some(UserId::new(42)),
+                TrackSource::Playlist(String::from("Mix")),
+            ));
+        }
+        queue.playing_index = 10;
+
+        let page = to_queue_page(&queue, 7, 10, 0);
+
+        assert_eq!(
+            page["embeds"][0]["footer"]["text"],
+            "Page 2 of 2 - 12 tracks"
+        );
+        assert_eq!(
+            page["embeds"][0]["description"],
+            "▶️ 11. Track 11 (01:00) - Unknown artist\n- 12. Track 12 (01:00) - Unknown artist · <@42>"
+        );
+        assert_eq!(page["components"][0]["components"][3]["disabled"], true);
+    }
+
+    #[test]
+    fn history_page_lists_newest_first_with_requeue_buttons() {
+        let entries: Vec<Entry> = (0..12)
+            .rev()
+            .map(|i| Entry {
+                url: format!("https://youtu.be/{}", i),
+                title: Some(format!("Track {}", i + 1)),
+                artist: None,
+                duration_secs: Some(200),
+                requester: (i == 0).then_some(UserId::new(42)),
+                started_at: 1_700_000_000_000 + i * 1000,
+                listened_secs: 75,
+            })
+            .collect();
+
+        let page = to_history_page(&entries, 1, 10, 0);
+
+        assert_eq!(
+            page["embeds"][0]["footer"]["text"],
+            "Page 2 of 2 - 12 tracks"
+        );
+        assert_eq!(
+            page["embeds"][0]["description"],
+            "11. Track 2 - Unknown artist · <t:1700000001:R> · 01:15/03:20\n12. Track 1 - Unknown artist · <t:1700000000:R> · 01:15/03:20 · <@42>"
+        );
+        assert_eq!(
+            page["components"][1]["components"][1]["custom_id"],
+            "history:play:1700000000000"
+        );
+        assert_eq!(page["components"][0]["components"][0]["disabled"], false);
+    }
+
+    #[test]
+    fn jump_menu_windows_around_current() {
+        let mut queue = Queue::default();
+        for i in 0..40 {
+            queue.queue.push(QueuedTrack::new(
+                AuxMetadata {
+                    title: Some(format!("Track {}", i + 1)),
+                    ..Default::default()
+                },
+                None,
+                TrackSource::Url,
+            ));
+        }
+        queue.playing_index = 30;
+
+        let options = jump_options(&queue);
+
+        assert_eq!(options.len(), JUMP_OPTIONS);
+        assert_eq!(options[0]["value"], "15");
+        assert_eq!(options[15]["label"], "31. Track 31");
+        assert_eq!(options[15]["default"], true);
+    }
+
+    #[test]
+    fn progress_bar_places_knob() {
+        let total = Duration::from_secs(100);
+
+        assert_eq!(progress_bar(Duration::ZERO, total, 5), "🔘▬▬▬▬");
+        assert_eq!(progress_bar(Duration::from_secs(50), total, 5), "▬▬🔘▬▬");
+        assert_eq!(progress_bar(total, total, 5), "▬▬▬▬🔘");
+        assert_eq!(progress_bar(Duration::ZERO, Duration::ZERO, 5), "🔘▬▬▬▬");
+    }
+}
@@ -1 +1,3 @@
-pub(crate) mod messages;
\ No newline at end of file
+#[allow(clippy::module_inception)]
+pub(crate) mod messages;
+pub(crate) mod progress;
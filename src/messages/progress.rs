@@ -0,0 +1,104 @@
+use crate::QueueKey;
+use crate::commands::play::songbird_manager;
+use crate::messages::messages::to_embed;
+use crate::settings::settings::settings;
+use crate::telemetry::metrics;
+use serenity::all::GuildId;
+use serenity::http::Http;
+use serenity::prelude::TypeMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, warn};
+
+/// Discord allows about five edits per five seconds on a channel, shared with user actions.
+const MIN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Periodically refreshes the now-playing message of every guild that is playing, so that the
+/// progress bar advances. The period is read from `BEAT_PROGRESS_INTERVAL` in seconds.
+pub fn spawn(data: Arc<RwLock<TypeMap>>, http: Arc<Http>) {
+    let period = env::var("BEAT_PROGRESS_INTERVAL")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(15))
+        .max(MIN_INTERVAL);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            refresh(&data, &http).await;
+        }
+    });
+}
+
+async fn refresh(data: &Arc<RwLock<TypeMap>>, http: &Http) {
+    let Ok(manager) = songbird_manager(data).await else {
+        return;
+    };
+
+    let queue_lock = {
+        let guard = data.read().await;
+        guard.get::<QueueKey>().cloned()
+    };
+
+    let Some(queue_lock) = queue_lock else {
+        return;
+    };
+
+    let playing: Vec<GuildId> = queue_lock
+        .read()
+        .await
+        .iter()
+        .filter(|(_, queue)| queue.message_id.is_some() && !queue.pause && !queue.stopping)
+        .map(|(guild_id, _)| *guild_id)
+        .collect();
+
+    for guild_id in playing {
+        let Some(handler_lock) = manager.get(guild_id) else {
+            continue;
+        };
+
+        let current = handler_lock.lock().await.queue().current();
+        let Some(info) = (match current {
+            Some(current) => current.get_info().await.ok(),
+            None => None,
+        }) else {
+            continue;
+        };
+
+        let settings = settings(data, guild_id).await;
+
+        // Render under the lock, but edit outside of it to not hold every command on Discord
+        let update = {
+            let mut maybe_queue = queue_lock.write().await;
+
+            match maybe_queue.get_mut(&guild_id) {
+                Some(queue) if !queue.pause && !queue.queue.is_empty() => {
+                    queue.position = info.position;
+                    queue
+                        .channel_id
+                        .zip(queue.message_id)
+                        .map(|ids| (ids, to_embed(queue, &settings)))
+                }
+                _ => None,
+            }
+        };
+
+        if let Some(((channel_id, message_id), embed)) = update {
+            http.edit_message(channel_id, message_id, &embed, vec![])
+                .await
+                .map(|_| debug!(%guild_id, "Refreshed progress"))
+                .map_err(|error| {
+                    metrics::discord_error();
+                    warn!(?error, %guild_id, "Failed to refresh progress")
+                })
+                .unwrap_or_default();
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use crate::QueueKey;
+use crate::errors::errors::BeatError;
+use crate::settings::settings::settings;
+use serenity::all::{GuildId, Interaction, Member, Permissions, ResolvedValue, RoleId, UserId};
+use serenity::client::Context;
+use std::env;
+use std::path::Path;
+
+/// What a member needs to use a command or component, each level including the previous ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Anyone,
+    /// In the voice channel Beat is playing in
+    Listener,
+    /// A listener with the DJ role, when the guild has one
+    Dj,
+    /// The guild owner or a member allowed to manage it
+    Owner,
+}
+
+/// Everything about the member needed to decide, gathered before any `.await`.
+struct Caller {
+    privileged: bool,
+    /// `None` when the guild has no DJ role
+    has_dj_role: Option<bool>,
+    in_bot_channel: bool,
+}
+
+/// Name of the DJ role when none is set in the guild settings, matched case-insensitively. Guilds
+/// without such a role let every listener act as a DJ, except for skipping which then goes through
+/// a vote.
+fn dj_role_name() -> String {
+    env::var("BEAT_DJ_ROLE").unwrap_or(String::from("DJ"))
+}
+
+/// Level required by a command or component, by name.
+pub fn required(interaction: &Interaction) -> Level {
+    match interaction {
+        Interaction::Command(command) => match command.data.name.as_str() {
+            // Non-DJs vote instead of skipping
+            "pause" | "loop" | "next" | "volume" => Level::Listener,
+            "prev" | "stop" | "seek" | "forward" | "rewind" | "filter" => Level::Dj,
+            "save" => {
+                // Saving is harmless, overwriting someone else's playlist is not
+                let overwrites = command.guild_id.is_some_and(|guild_id| {
+                    command.data.options().iter().any(|option| {
+                        option.name == "name"
+                            && matches!(
+                                option.value,
+                                ResolvedValue::String(name)
+                                    if Path::new(&format!("./{}/{}.playlist", guild_id, name)).exists()
+                            )
+                    })
+                });
+
+                if overwrites {
+                    Level::Dj
+                } else {
+                    Level::Listener
+                }
+            }
+            "play" => {
+                // Adding to the end of the queue is harmless, jumping it is not
+                let inserts = command
+                    .data
+                    .options()
+                    .iter()
+                    .any(|option| option.name == "position");
+
+                if inserts { Level::Dj } else { Level::Anyone }
+            }
+            "playnext" => Level::Dj,
+            "library" => {
+                // Scanning reads the whole library, searching is harmless
+                let scans = command
+                    .data
+                    .options()
+                    .iter()
+                    .any(|option| option.name == "scan");
+
+                if scans { Level::Dj } else { Level::Anyone }
+            }
+            "podcast" => {
+                // Listening is open to all, the followed podcasts are for DJs to pick
+                let follows = command
+                    .data
+                    .options()
+                    .iter()
+                    .any(|option| option.name == "subscribe" || option.name == "unsubscribe");
+
+                if follows { Level::Dj } else { Level::Anyone }
+            }
+            "clean" | "settings" | "delete" => Level::Owner,
+            _ => Level::Anyone,
+        },
+        Interaction::Component(component) => {
+            match component
+                .data
+                .custom_id
+                .split(':')
+                .next()
+                .unwrap_or_default()
+            {
+                "pause" | "loop" | "next" | "volume" => Level::Listener,
+                "prev" | "jump" | "stop" | "forward" | "rewind" => Level::Dj,
+                _ => Level::Anyone,
+            }
+        }
+        _ => Level::Anyone,
+    }
+}
+
+/// Checks that the member behind an interaction may use it, before it is dispatched.
+pub async fn check(ctx: &Context, interaction: &Interaction) -> Result<(), BeatError> {
+    let (guild_id, channel_id, member) = match interaction {
+        Interaction::Command(command) => (
+            command.guild_id,
+            command.channel_id,
+            command.member.as_deref(),
+        ),
+        Interaction::Component(component) => (
+            component.guild_id,
+            component.channel_id,
+            component.member.as_ref(),
+        ),
+        _ => return Ok(()),
+    };
+
+    let level = required(interaction);
+    let Some(guild_id) = guild_id else {
+        return match level {
+            Level::Anyone => Ok(()),
+            _ => Err(BeatError::NoGuild),
+        };
+    };
+
+    let settings = settings(&ctx.data, guild_id).await;
+
+    // Settings stay reachable from anywhere, to be able to move the binding
+    let is_settings =
+        matches!(interaction, Interaction::Command(command) if command.data.name == "settings");
+    if let Some(music_channel) = settings.music_channel
+        && channel_id != music_channel
+        && !is_settings
+    {
+        return Err(BeatError::Forbidden(
+            "Beat is bound to another channel, see /settings show",
+        ));
+    }
+
+    if level == Level::Anyone {
+        return Ok(());
+    }
+
+    let member = member.ok_or(BeatError::NoGuild)?;
+    let caller = caller(ctx, guild_id, member, settings.dj_role)?;
+
+    if caller.privileged {
+        return Ok(());
+    }
+    if level == Level::Owner {
+        return Err(BeatError::Forbidden(
+            "Only the server owner or managers can do that",
+        ));
+    }
+    if !caller.in_bot_channel {
+        return Err(BeatError::Forbidden("Join Beat's voice channel first"));
+    }
+    if level == Level::Dj && !caller.has_dj_role.unwrap_or(true) {
+        return Err(BeatError::Forbidden("Only a DJ can do that"));
+    }
+
+    Ok(())
+}
+
+fn caller(
+    ctx: &Context,
+    guild_id: GuildId,
+    member: &Member,
+    dj_role: Option<RoleId>,
+) -> Result<Caller, BeatError> {
+    let bot_id = ctx.cache.current_user().id;
+    let guild = ctx
+        .cache
+        .guild(guild_id)
+        .ok_or(BeatError::Other("Beat has no information about that guild"))?;
+
+    let permissions = member.permissions.unwrap_or_default();
+    let privileged = guild.owner_id == member.user.id
+        || permissions.contains(Permissions::ADMINISTRATOR)
+        || permissions.contains(Permissions::MANAGE_GUILD);
+
+    let has_dj_role = match dj_role {
+        Some(dj_role) => Some(member.roles.contains(&dj_role)),
+        None => {
+            let dj_role_name = dj_role_name();
+            guild
+                .roles
+                .values()
+                .find(|role| role.name.eq_ignore_ascii_case(&dj_role_name))
+                .map(|role| member.roles.contains(&role.id))
+        }
+    };
+
+    let channel_of = |user_id: &UserId| {
+        guild
+            .voice_states
+            .get(user_id)
+            .and_then(|voice_state| voice_state.channel_id)
+    };
+    // Nothing to disturb while Beat is not connected
+    let in_bot_channel = match channel_of(&bot_id) {
+        Some(bot_channel) => channel_of(&member.user.id) == Some(bot_channel),
+        None => true,
+    };
+
+    Ok(Caller {
+        privileged,
+        has_dj_role,
+        in_bot_channel,
+    })
+}
+
+/// Whether the member may skip without a vote: a manager, a DJ, or whoever queued the track.
+/// Unlike other DJ actions, guilds without a DJ role do not make everyone a DJ here.
+pub async fn may_skip(
+    ctx: &Context,
+    guild_id: GuildId,
+    member: &Member,
+) -> Result<bool, BeatError> {
+    let dj_role = settings(&ctx.data, guild_id).await.dj_role;
+    let caller = caller(ctx, guild_id, member, dj_role)?;
+
+    Ok(caller.privileged
+        || caller.has_dj_role == Some(true)
+        || requested_current(ctx, guild_id, member.user.id).await)
+}
+
+/// Whether the member queued the track currently playing.
+async fn requested_current(ctx: &Context, guild_id: GuildId, user_id: UserId) -> bool {
+    let queue_lock = {
+        let guard = ctx.data.read().await;
+        guard.get::<QueueKey>().cloned()
+    };
+
+    let Some(queue_lock) = queue_lock else {
+        return false;
+    };
+
+    let maybe_queue = queue_lock.read().await;
+    maybe_queue
+        .get(&guild_id)
+        .and_then(|queue| queue.queue.get(queue.playing_index))
+        .is_some_and(|track| track.is_requested_by(user_id))
+}
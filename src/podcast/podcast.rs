@@ -0,0 +1,561 @@
+use crate::errors::errors::BeatError;
+use quick_xml::XmlVersion;
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serenity::all::GuildId;
+use songbird::tracks::{PlayMode, TrackHandle};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::fs::create_dir_all;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{debug, info_span, warn};
+use tracing_futures::Instrument;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the position of a playing episode is saved.
+const SAVE_PERIOD: Duration = Duration::from_secs(10);
+/// Episodes stopped this close to their end are finished, and start over next time.
+const FINISHED_MARGIN: Duration = Duration::from_secs(60);
+
+/// Serializes the read-modify-write of the podcast files, saved from several tasks.
+static FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// An episode of a podcast, played from its enclosure.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Episode {
+    pub url: String,
+    pub title: Option<String>,
+    /// Title of the podcast
+    pub show: Option<String>,
+    /// Seconds since the Unix epoch
+    pub published: Option<u64>,
+    pub duration: Option<Duration>,
+}
+
+/// A parsed RSS or Atom feed, episodes newest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feed {
+    pub title: Option<String>,
+    pub episodes: Vec<Episode>,
+}
+
+/// Podcasts of a guild, persisted in `./<guild_id>/podcasts.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Podcasts {
+    #[serde(default)]
+    pub subscriptions: Vec<Subscription>,
+    /// Where the guild left each episode, in seconds, by enclosure URL
+    #[serde(default)]
+    pub positions: HashMap<String, u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub url: String,
+    pub title: String,
+}
+
+impl Podcasts {
+    /// Subscriptions whose title contains `query` ignoring case or whose link is `query`, or all
+    /// of them without one.
+    pub fn find(&self, query: Option<&str>) -> Vec<&Subscription> {
+        self.subscriptions
+            .iter()
+            .filter(|subscription| {
+                query.is_none_or(|query| {
+                    subscription
+                        .title
+                        .to_lowercase()
+                        .contains(&query.to_lowercase())
+                        || subscription.url == query
+                })
+            })
+            .collect()
+    }
+
+    pub fn position(&self, url: &str) -> Option<Duration> {
+        self.positions
+            .get(url)
+            .map(|position| Duration::from_secs(*position))
+    }
+}
+
+/// Short identifier of a feed or an episode by its link, enclosure links being often too long to
+/// fit in a component.
+pub fn short_id(url: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+fn file_name(guild_id: GuildId) -> String {
+    format!("./{}/podcasts.json", guild_id)
+}
+
+pub fn podcasts(guild_id: GuildId) -> Podcasts {
+    fs::read_to_string(file_name(guild_id))
+        .ok()
+        .and_then(|content| {
+            serde_json::from_str(&content)
+                .map_err(|error| warn!(?error, %guild_id, "Invalid podcasts file, ignoring it"))
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+/// Applies `change` to the podcasts of a guild and saves them.
+pub fn update(guild_id: GuildId, change: impl FnOnce(&mut Podcasts)) -> Result<(), BeatError> {
+    let _guard = FILE_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let mut podcasts = podcasts(guild_id);
+    change(&mut podcasts);
+
+    create_dir_all(format!("./{}", guild_id))?;
+    let content = serde_json::to_string_pretty(&podcasts)
+        .map_err(|_| BeatError::Other("Could not serialize podcasts"))?;
+    fs::write(file_name(guild_id), content)?;
+
+    Ok(())
+}
+
+pub async fn fetch(client: &Client, url: &str) -> Option<Feed> {
+    let content = client
+        .get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .text()
+        .await
+        .ok()?;
+
+    parse(&content)
+}
+
+/// Remembers where a playing episode is, every [`SAVE_PERIOD`] for as long as the track lives,
+/// so it resumes there even after the bot stopped. Watched from the moment it is queued, with
+/// the position it was `resumed` from.
+pub fn watch(
+    guild_id: GuildId,
+    track: TrackHandle,
+    url: String,
+    resumed: Option<Duration>,
+    duration: Option<Duration>,
+) {
+    tokio::spawn(
+        async move {
+            let mut saved = Progress::Unchanged;
+
+            loop {
+                tokio::time::sleep(SAVE_PERIOD).await;
+
+                // Ended tracks stop answering
+                let Ok(info) = track.get_info().await else {
+                    break;
+                };
+                if info.playing.is_done() {
+                    break;
+                }
+
+                let progress = progress(
+                    &info.playing,
+                    info.position,
+                    resumed.unwrap_or_default(),
+                    duration,
+                );
+                if progress == Progress::Unchanged || progress == saved {
+                    continue;
+                }
+                saved = progress;
+
+                debug!(%url, ?saved, "Saving episode position");
+                update(guild_id, |podcasts| match saved {
+                    Progress::At(position) => {
+                        podcasts.positions.insert(url.clone(), position);
+                    }
+                    Progress::Finished => {
+                        podcasts.positions.remove(&url);
+                    }
+                    Progress::Unchanged => {}
+                })
+                .map_err(|error| warn!(?error, "Failed to save episode position"))
+                .unwrap_or_default();
+            }
+        }
+        .instrument(info_span!("podcast", guild_id = guild_id.get())),
+    );
+}
+
+/// What to remember of an episode, every time it is checked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Progress {
+    /// Keep the stored position
+    Unchanged,
+    /// Seconds in
+    At(u64),
+    /// Close enough to the end to start over next time
+    Finished,
+}
+
+/// Progress of an episode `playing` at `position`. Queued episodes wait paused at the start, and
+/// resumed ones play from the start until their seek applies, neither may overwrite the stored
+/// position.
+fn progress(
+    playing: &PlayMode,
+    position: Duration,
+    resumed: Duration,
+    duration: Option<Duration>,
+) -> Progress {
+    if *playing != PlayMode::Play || position < resumed {
+        Progress::Unchanged
+    } else if duration.is_some_and(|duration| position + FINISHED_MARGIN >= duration) {
+        Progress::Finished
+    } else {
+        Progress::At(position.as_secs())
+    }
+}
+
+/// Item or entry being read.
+#[derive(Default)]
+struct Draft {
+    url: Option<String>,
+    title: Option<String>,
+    published: Option<u64>,
+    duration: Option<Duration>,
+}
+
+/// Reads an RSS 2.0 or Atom feed. Items without an audio enclosure are skipped.
+pub fn parse(xml: &str) -> Option<Feed> {
+    let mut reader = Reader::from_str(xml);
+    let mut path: Vec<String> = vec![];
+    let mut text = String::new();
+    let mut title = None;
+    let mut draft: Option<Draft> = None;
+    let mut drafts = vec![];
+
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(tag) => {
+                let name = String::from(tag.local_name().as_ref());
+                if name == "item" || name == "entry" {
+                    draft = Some(Draft::default());
+                }
+                if let Some(draft) = draft.as_mut() {
+                    read_enclosure(&name, &tag, draft);
+                }
+                path.push(name);
+                text.clear();
+            }
+            Event::Empty(tag) => {
+                if let Some(draft) = draft.as_mut() {
+                    read_enclosure(tag.local_name().as_ref(), &tag, draft);
+                }
+            }
+            Event::Text(content) => text.push_str(&content.xml10_content()),
+            Event::CData(content) => text.push_str(&content.xml10_content()),
+            Event::GeneralRef(reference) => match reference.resolve_char_ref() {
+                Ok(Some(character)) => text.push(character),
+                _ => text.push_str(resolve_predefined_entity(&reference).unwrap_or_default()),
+            },
+            Event::End(_) => {
+                let Some(name) = path.pop() else {
+                    continue;
+                };
+                let value = Some(text.trim().to_string()).filter(|value| !value.is_empty());
+                text.clear();
+
+                match (name.as_str(), draft.as_mut()) {
+                    ("item" | "entry", Some(_)) => drafts.extend(draft.take()),
+                    ("title", Some(draft)) => draft.title = value,
+                    ("pubDate", Some(draft)) => {
+                        draft.published = value.as_deref().and_then(parse_date)
+                    }
+                    ("published", Some(draft)) => {
+                        draft.published = value.as_deref().and_then(parse_date)
+                    }
+                    ("updated", Some(draft)) if draft.published.is_none() => {
+                        draft.published = value.as_deref().and_then(parse_date)
+                    }
+                    ("duration", Some(draft)) => {
+                        draft.duration = value.as_deref().and_then(parse_duration)
+                    }
+                    // The feed title, not the one of its image
+                    ("title", None) if path.len() <= 2 && title.is_none() => title = value,
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let mut episodes: Vec<Episode> = drafts
+        .into_iter()
+        .filter_map(|draft| {
+            Some(Episode {
+                url: draft.url?,
+                title: draft.title,
+                show: title.clone(),
+                published: draft.published,
+                duration: draft.duration,
+            })
+        })
+        .collect();
+    // Feeds are usually newest first already, undated episodes keep their order at the end
+    episodes.sort_by_key(|episode| std::cmp::Reverse(episode.published));
+
+    Some(Feed { title, episodes })
+}
+
+/// `<enclosure url type>` in RSS, `<link rel="enclosure" href type>` in Atom.
+fn read_enclosure(name: &str, tag: &BytesStart, draft: &mut Draft) {
+    let attribute = |key: &str| {
+        tag.try_get_attribute(key)
+            .ok()
+            .flatten()
+            .and_then(|attribute| attribute.normalized_value(XmlVersion::Implicit1_0).ok())
+            .map(|value| value.into_owned())
+    };
+    let is_media = attribute("type").is_none_or(|media_type| {
+        media_type.starts_with("audio/") || media_type.starts_with("video/")
+    });
+
+    let url = match name {
+        "enclosure" => attribute("url"),
+        "link" if attribute("rel").as_deref() == Some("enclosure") => attribute("href"),
+        _ => None,
+    };
+    if draft.url.is_none() && is_media {
+        draft.url = url;
+    }
+}
+
+/// Seconds since the Unix epoch of an RFC 2822 date as in RSS, e.g. `Tue, 10 Jun 2003 04:00:00
+/// GMT`, or an RFC 3339 one as in Atom, e.g. `2003-06-10T04:00:00+02:00`.
+fn parse_date(date: &str) -> Option<u64> {
+    let date = date.trim();
+
+    let (year, month, day, time, offset) = if date.as_bytes().get(4) == Some(&b'-') {
+        let (day, time) = date.split_once('T')?;
+        let mut day = day.split('-');
+        let (year, month, day) = (day.next()?, day.next()?, day.next()?);
+
+        let (time, offset) = match time.find(['Z', 'z', '+', '-']) {
+            Some(index) => (&time[..index], zone_offset(&time[index..])?),
+            None => (time, 0),
+        };
+        (
+            year.parse::<i64>().ok()?,
+            month.parse::<i64>().ok()?,
+            day.parse::<i64>().ok()?,
+            time,
+            offset,
+        )
+    } else {
+        // The weekday is optional and redundant
+        let date = date.split_once(',').map(|(_, rest)| rest).unwrap_or(date);
+        let mut parts = date.split_whitespace();
+        let day = parts.next()?.parse::<i64>().ok()?;
+        let month = parts.next()?.to_lowercase();
+        let month = [
+            "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+        ]
+        .iter()
+        .position(|name| month.starts_with(name))? as i64
+            + 1;
+        let year = parts.next()?.parse::<i64>().ok()?;
+        let time = parts.next()?;
+        let offset = parts.next().map(zone_offset).unwrap_or(Some(0))?;
+
+        (year, month, day, time, offset)
+    };
+
+    let mut time = time.split(':');
+    let hours = time.next()?.parse::<i64>().ok()?;
+    let minutes = time.next()?.parse::<i64>().ok()?;
+    let seconds = time
+        .next()
+        .and_then(|seconds| seconds.split('.').next())
+        .map(|seconds| seconds.parse::<i64>().ok())
+        .unwrap_or(Some(0))?;
+
+    let seconds =
+        days_from_civil(year, month, day) * 86_400 + hours * 3600 + minutes * 60 + seconds - offset;
+    u64::try_from(seconds).ok()
+}
+
+/// Offset from UTC in seconds of `Z`, `+0200`, `-05:00` or a North American zone name.
+fn zone_offset(zone: &str) -> Option<i64> {
+    let hours = match zone.to_uppercase().as_str() {
+        "Z" | "GMT" | "UT" | "UTC" => return Some(0),
+        "EDT" => -4,
+        "EST" | "CDT" => -5,
+        "CST" | "MDT" => -6,
+        "MST" | "PDT" => -7,
+        "PST" => -8,
+        _ => {
+            let sign = match zone.chars().next()? {
+                '+' => 1,
+                '-' => -1,
+                _ => return None,
+            };
+            let digits: String = zone[1..].chars().filter(char::is_ascii_digit).collect();
+            if digits.len() != 4 {
+                return None;
+            }
+            let hours = digits[..2].parse::<i64>().ok()?;
+            let minutes = digits[2..].parse::<i64>().ok()?;
+            return Some(sign * (hours * 3600 + minutes * 60));
+        }
+    };
+
+    Some(hours * 3600)
+}
+
+/// Days since the Unix epoch of a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// `itunes:duration`, given in seconds or as `[HH:]MM:SS`.
+fn parse_duration(duration: &str) -> Option<Duration> {
+    duration
+        .split(':')
+        .try_fold(0, |seconds, part| {
+            part.trim()
+                .split('.')
+                .next()?
+                .parse::<u64>()
+                .ok()
+                .map(|part| seconds * 60 + part)
+        })
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rss_feeds() {
+        let feed = parse(include_str!("../../tests/feeds/rss.xml")).unwrap();
+
+        assert_eq!(feed.title.as_deref(), Some("Rust & Friends"));
+        // The items without an audio enclosure are skipped
+        assert_eq!(feed.episodes.len(), 3);
+
+        let latest = &feed.episodes[0];
+        assert_eq!(latest.title.as_deref(), Some("Episode 3: <Async> & you"));
+        assert_eq!(
+            latest.url,
+            "https://cdn.example.com/episodes/3.mp3?source=rss"
+        );
+        assert_eq!(latest.show.as_deref(), Some("Rust & Friends"));
+        assert_eq!(latest.published, Some(1_717_243_200));
+        assert_eq!(latest.duration, Some(Duration::from_secs(3723)));
+
+        // Out of order in the file, sorted newest first
+        assert_eq!(feed.episodes[1].title.as_deref(), Some("Episode 2"));
+        assert_eq!(feed.episodes[1].duration, Some(Duration::from_secs(1800)));
+        assert_eq!(feed.episodes[2].title.as_deref(), Some("Episode 1"));
+        assert_eq!(feed.episodes[2].duration, Some(Duration::from_secs(754)));
+    }
+
+    #[test]
+    fn parses_atom_feeds() {
+        let feed = parse(include_str!("../../tests/feeds/atom.xml")).unwrap();
+
+        assert_eq!(feed.title.as_deref(), Some("Atomic Talks"));
+        assert_eq!(feed.episodes.len(), 2);
+        assert_eq!(feed.episodes[0].title.as_deref(), Some("Second talk"));
+        assert_eq!(feed.episodes[0].url, "https://example.org/talks/2.ogg");
+        assert_eq!(feed.episodes[0].published, Some(1_717_236_000));
+        assert_eq!(feed.episodes[1].title.as_deref(), Some("First talk"));
+        // Only updated, without a published date
+        assert_eq!(feed.episodes[1].published, Some(1_704_067_200));
+    }
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(
+            parse_date("Sat, 01 Jun 2024 12:00:00 GMT"),
+            Some(1_717_243_200)
+        );
+        assert_eq!(parse_date("1 Jun 2024 08:00:00 -0400"), Some(1_717_243_200));
+        assert_eq!(
+            parse_date("Sat, 01 Jun 2024 05:00 PDT"),
+            Some(1_717_243_200)
+        );
+        assert_eq!(parse_date("2024-06-01T14:00:00+02:00"), Some(1_717_243_200));
+        assert_eq!(parse_date("2024-06-01T12:00:00.250Z"), Some(1_717_243_200));
+        assert_eq!(parse_date("yesterday"), None);
+    }
+
+    #[test]
+    fn finds_subscriptions_by_title_or_link() {
+        let podcasts = Podcasts {
+            subscriptions: vec![Subscription {
+                url: String::from("https://Feeds.example.com/RustAndFriends.xml"),
+                title: String::from("Rust & Friends"),
+            }],
+            ..Podcasts::default()
+        };
+
+        assert_eq!(podcasts.find(Some("rust &")).len(), 1);
+        assert_eq!(
+            podcasts
+                .find(Some("https://Feeds.example.com/RustAndFriends.xml"))
+                .len(),
+            1
+        );
+        assert!(podcasts.find(Some("python")).is_empty());
+        assert_eq!(podcasts.find(None).len(), 1);
+    }
+
+    #[test]
+    fn saves_only_what_was_played_past_the_resume_position() {
+        let resumed = Duration::from_secs(600);
+        let duration = Some(Duration::from_secs(3600));
+        let at = Duration::from_secs;
+
+        // Still waiting in the queue
+        assert_eq!(
+            progress(&PlayMode::Pause, at(0), resumed, duration),
+            Progress::Unchanged
+        );
+        // Playing, before the seek to the resume position applied
+        assert_eq!(
+            progress(&PlayMode::Play, at(0), resumed, duration),
+            Progress::Unchanged
+        );
+        assert_eq!(
+            progress(&PlayMode::Play, at(612), resumed, duration),
+            Progress::At(612)
+        );
+        assert_eq!(
+            progress(&PlayMode::Play, at(3590), resumed, duration),
+            Progress::Finished
+        );
+        // Never played before
+        assert_eq!(
+            progress(&PlayMode::Play, at(10), Duration::ZERO, duration),
+            Progress::At(10)
+        );
+    }
+}
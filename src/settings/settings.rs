@@ -0,0 +1,259 @@
+use crate::errors::errors::BeatError;
+use serde::{Deserialize, Serialize};
+use serenity::all::{ChannelId, GuildId, RoleId};
+use serenity::prelude::{TypeMap, TypeMapKey};
+use std::collections::HashMap;
+use std::fs;
+use std::fs::create_dir_all;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+/// Per-guild settings, loaded lazily from `./<guild_id>/settings.json`.
+pub struct SettingsKey;
+
+impl TypeMapKey for SettingsKey {
+    type Value = Arc<RwLock<HashMap<GuildId, Settings>>>;
+}
+
+/// Volume bounds, in percent.
+pub const MIN_VOLUME: u8 = 0;
+pub const MAX_VOLUME: u8 = 200;
+
+/// How much the now-playing message shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verbosity {
+    /// The current track and its progress only
+    Compact,
+    /// Also the upcoming and previous tracks, and who requested the current one
+    Normal,
+    /// Also who requested each of the listed tracks
+    Detailed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    English,
+    French,
+}
+
+/// How one track leads into the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transition {
+    /// The next track loads early in the current one and starts once it ended
+    Cut,
+    /// The next track loads right before the current one ends, to start without a gap
+    Gapless,
+    /// The next track starts before the current one ends, their volumes ramping over
+    Crossfade,
+}
+
+/// Settings of a guild. Missing fields fall back to their default, so older files keep loading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Volume of new tracks, in percent
+    pub default_volume: u8,
+    /// Channel the commands and the now-playing message are bound to
+    pub music_channel: Option<ChannelId>,
+    /// Role allowed to skip, go back, jump and stop, otherwise looked up by name
+    pub dj_role: Option<RoleId>,
+    /// Fraction of the listeners who must vote for a skip without a DJ to go through
+    pub vote_skip_ratio: f64,
+    pub max_queue_length: Option<usize>,
+    pub max_track_duration_secs: Option<u64>,
+    /// Seconds to stay connected once the queue ends, 0 to leave right away
+    pub idle_timeout_secs: u64,
+    pub verbosity: Verbosity,
+    pub language: Language,
+    /// Seconds into a track to start loading the next one, or before its end unless cutting
+    pub preload_secs: u64,
+    /// Same, for tracks recreated when going back in the queue
+    pub rebuild_preload_secs: u64,
+    /// Tracks listed before and after the current one in the now-playing message
+    pub preview_tracks: usize,
+    /// yt-dlp `-f` format selector
+    pub ytdl_format: String,
+    /// Whether to even out the loudness of tracks, towards `target_lufs`
+    pub normalize: bool,
+    pub target_lufs: f32,
+    pub transition: Transition,
+    /// Overlap of two tracks when crossfading
+    pub crossfade_secs: u64,
+    /// Whether `/play` also suggests YouTube results while typing
+    pub search_suggestions: bool,
+    /// Whether related tracks keep playing once the queue runs out
+    pub autoplay: bool,
+    /// Tracks autoplayed in a row without a listener doing anything, before leaving
+    pub autoplay_limit: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            default_volume: 100,
+            music_channel: None,
+            dj_role: None,
+            vote_skip_ratio: 0.5,
+            max_queue_length: None,
+            max_track_duration_secs: None,
+            idle_timeout_secs: 0,
+            verbosity: Verbosity::Normal,
+            language: Language::English,
+            preload_secs: 10,
+            rebuild_preload_secs: 15,
+            preview_tracks: 2,
+            ytdl_format: String::from("webm[abr>0]/bestaudio/best"),
+            normalize: false,
+            target_lufs: -14.0,
+            transition: Transition::Cut,
+            crossfade_secs: 5,
+            search_suggestions: false,
+            autoplay: false,
+            autoplay_limit: 10,
+        }
+    }
+}
+
+impl Settings {
+    /// Playback time of a track of `duration` at which to load the next one.
+    pub fn preload(&self, duration: Option<Duration>) -> Duration {
+        self.preload_at(Duration::from_secs(self.preload_secs), duration)
+    }
+
+    pub fn rebuild_preload(&self, duration: Option<Duration>) -> Duration {
+        self.preload_at(Duration::from_secs(self.rebuild_preload_secs), duration)
+    }
+
+    pub fn crossfade(&self) -> Duration {
+        Duration::from_secs(self.crossfade_secs)
+    }
+
+    /// Lines the loading up with the moment the next track has to play, which livestreams never
+    /// reach.
+    fn preload_at(&self, lead: Duration, duration: Option<Duration>) -> Duration {
+        match (self.transition, duration) {
+            (Transition::Cut, _) | (_, None) => lead,
+            (Transition::Gapless, Some(duration)) => duration.saturating_sub(lead),
+            (Transition::Crossfade, Some(duration)) => {
+                duration.saturating_sub(lead + self.crossfade())
+            }
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.default_volume as f32 / 100.0
+    }
+
+    /// Value of the yt-dlp `-f` argument.
+    pub fn ytdl_format_arg(&self) -> String {
+        format!("\"{}\"", self.ytdl_format)
+    }
+}
+
+fn file_name(guild_id: GuildId) -> String {
+    format!("./{}/settings.json", guild_id)
+}
+
+fn load(guild_id: GuildId) -> Settings {
+    match fs::read_to_string(file_name(guild_id)) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|error| warn!(?error, %guild_id, "Invalid settings, using defaults"))
+            .unwrap_or_default(),
+        Err(_) => Settings::default(),
+    }
+}
+
+fn store(guild_id: GuildId, settings: &Settings) -> Result<(), BeatError> {
+    create_dir_all(format!("./{}", guild_id))?;
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|_| BeatError::Other("Could not serialize settings"))?;
+    fs::write(file_name(guild_id), content)?;
+
+    Ok(())
+}
+
+/// Settings of a guild, read from disk the first time they are needed.
+pub async fn settings(data: &Arc<RwLock<TypeMap>>, guild_id: GuildId) -> Settings {
+    let settings_lock = {
+        let guard = data.read().await;
+        guard.get::<SettingsKey>().cloned()
+    };
+
+    let Some(settings_lock) = settings_lock else {
+        return Settings::default();
+    };
+
+    if let Some(settings) = settings_lock.read().await.get(&guild_id) {
+        return settings.clone();
+    }
+
+    settings_lock
+        .write()
+        .await
+        .entry(guild_id)
+        .or_insert_with(|| load(guild_id))
+        .clone()
+}
+
+/// Applies a change to the settings of a guild and persists them.
+pub async fn update(
+    data: &Arc<RwLock<TypeMap>>,
+    guild_id: GuildId,
+    change: impl FnOnce(&mut Settings),
+) -> Result<Settings, BeatError> {
+    let settings_lock = {
+        let guard = data.read().await;
+        guard
+            .get::<SettingsKey>()
+            .ok_or(BeatError::Other("Settings not initialized"))?
+            .clone()
+    };
+
+    let mut all_settings = settings_lock.write().await;
+    let settings = all_settings
+        .entry(guild_id)
+        .or_insert_with(|| load(guild_id));
+
+    change(settings);
+    store(guild_id, settings)?;
+    debug!(?settings, "Settings updated");
+
+    Ok(settings.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_fall_back_to_defaults() {
+        let settings: Settings =
+            serde_json::from_str(r#"{"default_volume": 50, "verbosity": "compact"}"#).unwrap();
+
+        assert_eq!(settings.default_volume, 50);
+        assert_eq!(settings.verbosity, Verbosity::Compact);
+        assert_eq!(settings.preload_secs, 10);
+        assert_eq!(settings.ytdl_format, "webm[abr>0]/bestaudio/best");
+        assert_eq!(settings.transition, Transition::Cut);
+        assert_eq!(settings.vote_skip_ratio, 0.5);
+    }
+
+    #[test]
+    fn preloads_before_the_next_track_plays() {
+        let duration = Some(Duration::from_secs(200));
+        let mut settings = Settings::default();
+        assert_eq!(settings.preload(duration), Duration::from_secs(10));
+
+        settings.transition = Transition::Gapless;
+        assert_eq!(settings.preload(duration), Duration::from_secs(190));
+        assert_eq!(settings.preload(None), Duration::from_secs(10));
+
+        settings.transition = Transition::Crossfade;
+        assert_eq!(settings.rebuild_preload(duration), Duration::from_secs(180));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::QueueKey;
+use serde_json::json;
+use serenity::gateway::{ConnectionStage, ShardManager};
+use serenity::json::Value;
+use serenity::prelude::TypeMap;
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::sync::{Mutex, RwLock};
+use tokio::time::timeout;
+use tracing::{debug, info, warn};
+
+const PROBE_INTERVAL: Duration = Duration::from_secs(5);
+const YTDLP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Periodically probes the gateway, the queue lock and yt-dlp, and keeps the latest report.
+pub struct Health {
+    shard_manager: Arc<ShardManager>,
+    data: Arc<RwLock<TypeMap>>,
+    max_heartbeat_age: Duration,
+    lock_timeout: Duration,
+    state: Mutex<ProbeState>,
+}
+
+struct ProbeState {
+    last_latency: Option<Duration>,
+    last_ack: Instant,
+    ytdlp_ok: bool,
+    ytdlp_checked: Option<Instant>,
+    report: Option<Report>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub at: Instant,
+    pub gateway_connected: bool,
+    pub heartbeat_age: Duration,
+    pub queue_lock_ok: bool,
+    pub ytdlp_ok: bool,
+    max_heartbeat_age: Duration,
+}
+
+impl Report {
+    /// The process is making progress: heartbeats are acknowledged and the queue lock is free.
+    pub fn is_live(&self) -> bool {
+        self.heartbeat_age <= self.max_heartbeat_age && self.queue_lock_ok
+    }
+
+    /// The bot can serve commands: it is live, connected, and yt-dlp can be run.
+    pub fn is_ready(&self) -> bool {
+        self.is_live() && self.gateway_connected && self.ytdlp_ok
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "live": self.is_live(),
+            "ready": self.is_ready(),
+            "gateway_connected": self.gateway_connected,
+            "heartbeat_age_secs": self.heartbeat_age.as_secs(),
+            "queue_lock_ok": self.queue_lock_ok,
+            "ytdlp_ok": self.ytdlp_ok,
+            "report_age_secs": self.at.elapsed().as_secs(),
+        })
+    }
+}
+
+impl Health {
+    /// Limits are read from `BEAT_HEALTH_MAX_HEARTBEAT_AGE` and `BEAT_HEALTH_LOCK_TIMEOUT`,
+    /// both in seconds.
+    pub fn new(shard_manager: Arc<ShardManager>, data: Arc<RwLock<TypeMap>>) -> Self {
+        let seconds = |key: &str, default: u64| {
+            env::var(key)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(Duration::from_secs(default))
+        };
+
+        Health {
+            shard_manager,
+            data,
+            max_heartbeat_age: seconds("BEAT_HEALTH_MAX_HEARTBEAT_AGE", 120),
+            lock_timeout: seconds("BEAT_HEALTH_LOCK_TIMEOUT", 5),
+            state: Mutex::new(ProbeState {
+                last_latency: None,
+                last_ack: Instant::now(),
+                ytdlp_ok: false,
+                ytdlp_checked: None,
+                report: None,
+            }),
+        }
+    }
+
+    /// Latest report, treated as failing if the probe loop itself stopped updating it.
+    pub async fn report(&self) -> Option<Report> {
+        let report = self.state.lock().await.report.clone()?;
+
+        if report.at.elapsed() > PROBE_INTERVAL * 3 {
+            // The probe loop is stuck, most likely on one of the locks it checks
+            return Some(Report {
+                queue_lock_ok: false,
+                ..report
+            });
+        }
+
+        Some(report)
+    }
+
+    async fn probe(&self) -> Report {
+        let (gateway_connected, latency) = {
+            let runners = self.shard_manager.runners.lock().await;
+            let connected = !runners.is_empty()
+                && runners
+                    .values()
+                    .all(|runner| runner.stage == ConnectionStage::Connected);
+            let latency = runners.values().filter_map(|runner| runner.latency).max();
+            (connected, latency)
+        };
+
+        let queue_lock = {
+            let guard = self.data.read().await;
+            guard.get::<QueueKey>().cloned()
+        };
+        let queue_lock_ok = match queue_lock {
+            Some(queue_lock) => timeout(self.lock_timeout, queue_lock.read()).await.is_ok(),
+            None => false,
+        };
+
+        let check_ytdlp = self
+            .state
+            .lock()
+            .await
+            .ytdlp_checked
+            .is_none_or(|checked| checked.elapsed() >= YTDLP_INTERVAL);
+        let ytdlp_ok = if check_ytdlp {
+            Some(ytdlp_reachable().await)
+        } else {
+            None
+        };
+
+        let mut state = self.state.lock().await;
+
+        // Serenity only refreshes the shard latency when a heartbeat ACK arrives, so a changed
+        // value is the closest observable signal of a fresh heartbeat.
+        if latency.is_some() && latency != state.last_latency {
+            state.last_latency = latency;
+            state.last_ack = Instant::now();
+        }
+
+        if let Some(ytdlp_ok) = ytdlp_ok {
+            state.ytdlp_ok = ytdlp_ok;
+            state.ytdlp_checked = Some(Instant::now());
+        }
+
+        let report = Report {
+            at: Instant::now(),
+            gateway_connected,
+            heartbeat_age: state.last_ack.elapsed(),
+            queue_lock_ok,
+            ytdlp_ok: state.ytdlp_ok,
+            max_heartbeat_age: self.max_heartbeat_age,
+        };
+
+        state.report = Some(report.clone());
+
+        report
+    }
+}
+
+async fn ytdlp_reachable() -> bool {
+    match timeout(
+        Duration::from_secs(10),
+        Command::new("yt-dlp").arg("--version").output(),
+    )
+    .await
+    {
+        Ok(Ok(output)) => output.status.success(),
+        Ok(Err(error)) => {
+            warn!(?error, "yt-dlp could not be run");
+            false
+        }
+        Err(_) => {
+            warn!("yt-dlp did not answer in time");
+            false
+        }
+    }
+}
+
+/// Tells systemd the service is up; a no-op when not started by systemd.
+pub fn notify_ready() {
+    sd_notify::notify(false, &[sd_notify::NotifyState::Ready])
+        .map_err(|error| debug!(?error, "sd_notify unavailable"))
+        .unwrap_or_default();
+}
+
+/// Runs the probes forever, pinging the systemd watchdog only while the bot is live so that
+/// systemd restarts a hung process.
+pub fn spawn(health: Arc<Health>) {
+    let mut usec = 0;
+    let watchdog =
+        sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec) / 2);
+
+    if let Some(interval) = watchdog {
+        info!(?interval, "systemd watchdog enabled");
+    }
+
+    let period = watchdog.map_or(PROBE_INTERVAL, |interval| interval.min(PROBE_INTERVAL));
+
+    tokio::spawn(async move {
+        loop {
+            let report = health.probe().await;
+
+            if !report.is_live() {
+                warn!(?report, "Health check failed");
+            } else if watchdog.is_some() {
+                sd_notify::notify(false, &[sd_notify::NotifyState::Watchdog])
+                    .map_err(|error| warn!(?error, "Failed to ping systemd watchdog"))
+                    .unwrap_or_default();
+            }
+
+            tokio::time::sleep(period).await;
+        }
+    });
+}
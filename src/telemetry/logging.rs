@@ -0,0 +1,124 @@
+use serenity::all::{ChannelId, GuildId, UserId};
+use std::env;
+use tracing::{Span, info_span};
+use tracing_subscriber::EnvFilter;
+
+/// Installs the global `tracing` subscriber.
+///
+/// The filter is read from `BEAT_LOG` (falling back to `RUST_LOG`, then `info`), so a single
+/// guild can be isolated with e.g. `BEAT_LOG=beat[{guild_id=1234}]=debug`, which selects its
+/// interactions, track events and background tasks alike.
+/// `BEAT_LOG_FORMAT` selects the output: `pretty`, `compact`, `json` or the default `full`.
+pub fn init() {
+    let filter = env::var("BEAT_LOG")
+        .or_else(|_| env::var("RUST_LOG"))
+        .map(EnvFilter::new)
+        .unwrap_or_else(|_| EnvFilter::new("info"));
+
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match env::var("BEAT_LOG_FORMAT")
+        .unwrap_or_default()
+        .to_lowercase()
+        .as_str()
+    {
+        "json" => builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .init(),
+        "pretty" => builder.pretty().init(),
+        "compact" => builder.compact().init(),
+        _ => builder.init(),
+    }
+}
+
+/// Span wrapping the handling of one interaction.
+///
+/// Ids are recorded as plain integers rather than their `Debug` form, as `EnvFilter` only
+/// matches a directive such as `guild_id=1234` against fields recorded as numbers.
+pub fn interaction_span(
+    kind: &str,
+    command: &str,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    user_id: UserId,
+) -> Span {
+    info_span!(
+        "interaction",
+        kind,
+        command,
+        guild_id = guild_id.map(GuildId::get),
+        channel_id = channel_id.get(),
+        user_id = user_id.get(),
+    )
+}
+
+/// Span wrapping the handling of a Songbird track event, with ids recorded like
+/// [`interaction_span`].
+pub fn track_event_span(event: &str, guild_id: GuildId, channel_id: ChannelId) -> Span {
+    info_span!(
+        "track_event",
+        event,
+        guild_id = guild_id.get(),
+        channel_id = channel_id.get(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tracing::debug;
+    use tracing_subscriber::Layer;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+
+    struct Count(Arc<AtomicUsize>);
+
+    impl<S: tracing::Subscriber> Layer<S> for Count {
+        fn on_event(&self, _: &tracing::Event<'_>, _: Context<'_, S>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn guild_directive_selects_its_interactions() {
+        let events = Arc::new(AtomicUsize::new(0));
+        let subscriber = tracing_subscriber::registry()
+            .with(EnvFilter::new("beat[interaction{guild_id=1234}]=debug"))
+            .with(Count(events.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let user = UserId::new(7);
+            let channel = ChannelId::new(9);
+
+            interaction_span("command", "play", Some(GuildId::new(1234)), channel, user)
+                .in_scope(|| debug!("selected"));
+            interaction_span("command", "play", Some(GuildId::new(4321)), channel, user)
+                .in_scope(|| debug!("other guild"));
+            interaction_span("command", "play", None, channel, user)
+                .in_scope(|| debug!("direct message"));
+        });
+
+        assert_eq!(events.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn guild_directive_selects_its_track_events() {
+        let events = Arc::new(AtomicUsize::new(0));
+        let subscriber = tracing_subscriber::registry()
+            .with(EnvFilter::new("beat[{guild_id=1234}]=debug"))
+            .with(Count(events.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let channel = ChannelId::new(9);
+
+            track_event_span("end", GuildId::new(1234), channel).in_scope(|| debug!("selected"));
+            track_event_span("end", GuildId::new(4321), channel)
+                .in_scope(|| debug!("other guild"));
+        });
+
+        assert_eq!(events.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,154 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use serenity::all::GuildId;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tracing::warn;
+
+/// All metrics exposed on `/metrics`, registered once on first use.
+pub struct Metrics {
+    registry: Registry,
+    voice_connections: IntGauge,
+    queue_length: IntGaugeVec,
+    tracks_played: IntCounter,
+    resolve_latency: HistogramVec,
+    resolve_failures: IntCounterVec,
+    commands: IntCounterVec,
+    discord_errors: IntCounter,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("beat".into()), None)
+            .expect("Static registry options are valid");
+
+        let voice_connections =
+            IntGauge::new("voice_connections", "Active voice connections").unwrap();
+        let queue_length = IntGaugeVec::new(
+            Opts::new("queue_length", "Tracks in the queue of each guild"),
+            &["guild_id"],
+        )
+        .unwrap();
+        let tracks_played =
+            IntCounter::new("tracks_played_total", "Tracks that started playing").unwrap();
+        let resolve_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "ytdlp_resolve_seconds",
+                "Time spent by yt-dlp resolving a track or playlist",
+            )
+            .buckets(vec![0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0]),
+            &["kind"],
+        )
+        .unwrap();
+        let resolve_failures = IntCounterVec::new(
+            Opts::new("ytdlp_failures_total", "yt-dlp resolutions that failed"),
+            &["kind"],
+        )
+        .unwrap();
+        let commands = IntCounterVec::new(
+            Opts::new("commands_total", "Handled commands by name and result"),
+            &["command", "result"],
+        )
+        .unwrap();
+        let discord_errors =
+            IntCounter::new("discord_api_errors_total", "Failed Discord API calls").unwrap();
+
+        registry
+            .register(Box::new(voice_connections.clone()))
+            .unwrap();
+        registry.register(Box::new(queue_length.clone())).unwrap();
+        registry.register(Box::new(tracks_played.clone())).unwrap();
+        registry
+            .register(Box::new(resolve_latency.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(resolve_failures.clone()))
+            .unwrap();
+        registry.register(Box::new(commands.clone())).unwrap();
+        registry.register(Box::new(discord_errors.clone())).unwrap();
+
+        Metrics {
+            registry,
+            voice_connections,
+            queue_length,
+            tracks_played,
+            resolve_latency,
+            resolve_failures,
+            commands,
+            discord_errors,
+        }
+    }
+}
+
+pub fn voice_connected() {
+    METRICS.voice_connections.inc();
+}
+
+pub fn voice_disconnected() {
+    METRICS.voice_connections.dec();
+}
+
+pub fn queue_length(guild_id: GuildId, length: usize) {
+    METRICS
+        .queue_length
+        .with_label_values(&[&guild_id.to_string()])
+        .set(length as i64);
+}
+
+pub fn track_played() {
+    METRICS.tracks_played.inc();
+}
+
+pub fn resolved(kind: &str, elapsed: Duration) {
+    METRICS
+        .resolve_latency
+        .with_label_values(&[kind])
+        .observe(elapsed.as_secs_f64());
+}
+
+pub fn resolve_failed(kind: &str) {
+    METRICS.resolve_failures.with_label_values(&[kind]).inc();
+}
+
+pub fn command(name: &str, success: bool) {
+    METRICS
+        .commands
+        .with_label_values(&[name, if success { "ok" } else { "error" }])
+        .inc();
+}
+
+pub fn discord_error() {
+    METRICS.discord_errors.inc();
+}
+
+/// Renders every metric in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut buffer = vec![];
+
+    TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buffer)
+        .map_err(|error| warn!(?error, "Failed to encode metrics"))
+        .unwrap_or_default();
+
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_prefixed_metrics() {
+        command("play", true);
+        queue_length(GuildId::new(42), 3);
+
+        let rendered = render();
+
+        assert!(rendered.contains("beat_commands_total{command=\"play\",result=\"ok\"}"));
+        assert!(rendered.contains("beat_queue_length{guild_id=\"42\"} 3"));
+    }
+}
@@ -0,0 +1,3 @@
+pub(crate) mod health;
+pub(crate) mod logging;
+pub(crate) mod metrics;